@@ -1,230 +1,1169 @@
-use http::header::{AUTHORIZATION, CONTENT_TYPE};
-use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::thread;
+use std::time::Duration;
+
+use http::header::{AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+
+mod due;
+mod resources;
+pub use due::*;
+pub use resources::*;
+
+#[derive(Debug)]
+/// Everything that can go wrong talking to the Todoist API.
+/// Each variant carries the request that failed, in the form "POST <url>"
+pub enum ApiError {
+    /// The request never got a response, e.g. no connection or a timeout
+    Network {
+        request: String,
+        source: reqwest::Error,
+    },
+    /// The server responded with a non-success status code
+    Http {
+        request: String,
+        status: u16,
+        body: String,
+    },
+    /// The response body could not be parsed into the expected objects
+    Parse {
+        request: String,
+        body: String,
+        source: serde_json::Error,
+    },
+    /// The sync endpoint accepted the request but rejected commands in it,
+    /// holds the name of each failed command alongside why it failed
+    Rejected {
+        request: String,
+        failures: Vec<(String, CommandError)>,
+    },
+}
+
+impl ApiError {
+    pub fn status(&self) -> Option<u16> {
+        //! HTTP status code of the response, if there was one
+        match self {
+            ApiError::Http { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    pub fn is_auth(&self) -> bool {
+        //! True if the token was missing or invalid. A 403 only means this one request
+        //! wasn't allowed, e.g. a premium feature, so it isn't counted
+        matches!(self.status(), Some(401))
+    }
+
+    pub fn is_rate_limited(&self) -> bool {
+        matches!(self.status(), Some(429))
+    }
+
+    pub fn is_server_error(&self) -> bool {
+        matches!(self.status(), Some(500..=599))
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Network { request, source } => {
+                write!(f, "{request} failed to send: {source}")
+            }
+            ApiError::Http {
+                request,
+                status,
+                body,
+            } => write!(f, "{request} returned HTTP {status}: {body}"),
+            ApiError::Parse {
+                request,
+                body,
+                source,
+            } => write!(
+                f,
+                "{request} returned a response that could not be parsed: {source}\n{}",
+                body.chars().take(200).collect::<String>()
+            ),
+            ApiError::Rejected { request, failures } => {
+                write!(f, "{request} had commands rejected:")?;
+                for (command, error) in failures {
+                    write!(f, " {command}: {error};")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ApiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ApiError::Network { source, .. } => Some(source),
+            ApiError::Parse { source, .. } => Some(source),
+            ApiError::Http { .. } | ApiError::Rejected { .. } => None,
+        }
+    }
+}
 
 #[derive(Clone, Serialize, Deserialize)]
 /// Represents due json object
 pub struct Due {
+    /// empty while a string typed in edit mode is waiting to be parsed by the server
     date: String,
+    /// natural language form, e.g. "every monday 9am"
+    #[serde(default)]
     string: String,
+    #[serde(default)]
+    is_recurring: bool,
+    #[serde(default)]
+    timezone: Option<String>,
+    /// language string is written in
+    #[serde(default)]
+    lang: Option<String>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
-/// Task representation: DOES NOT YET IMPLEMENT LABELS
+/// Task representation
 pub struct Task {
     id: String,
     content: String,
     description: String,
     checked: bool,
+    #[serde(default)]
+    is_deleted: bool,
     labels: Vec<String>,
     priority: u8,
     due: Option<Due>,
+    #[serde(default)]
+    project_id: String,
+    #[serde(default)]
+    section_id: Option<String>,
+    #[serde(default)]
+    parent_id: Option<String>,
+    #[serde(default)]
+    child_order: i64,
+    /// how long the task is expected to take
+    #[serde(default)]
+    duration: Option<TaskDuration>,
+    /// date the task has to be done by, separate from when it is due
+    #[serde(default)]
+    deadline: Option<Deadline>,
+}
+
+#[derive(Clone, Deserialize)]
+/// A task from the completed tasks endpoint, along with when it was completed
+pub struct CompletedTask {
+    #[serde(flatten)]
+    pub task: Task,
+    pub completed_at: String,
+}
+
+#[derive(Deserialize)]
+/// One page of results from an endpoint paged with a cursor
+struct Page<T> {
+    items: Vec<T>,
+    /// None on the last page
+    #[serde(default)]
+    next_cursor: Option<String>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
-/// Represents json returned from a sync request
-struct SyncResponse {
-    full_sync: bool,
-    items: Vec<Task>,
-    sync_token: String,
+/// Represents duration json object, unit is "minute" or "day"
+pub struct TaskDuration {
+    amount: u32,
+    unit: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+/// Represents deadline json object, dates only without a time
+pub struct Deadline {
+    date: String,
+}
+
+/// Fields of a task that edit mode can change
+pub struct TaskEdit {
+    pub content: String,
+    pub description: String,
+    /// natural language due string, empty to remove the due date
+    pub due_string: String,
+    pub labels: Vec<String>,
+    pub priority: u8,
+    pub duration: Option<TaskDuration>,
+    pub deadline: Option<Deadline>,
+}
+
+impl TaskDuration {
+    pub fn parse(text: &str) -> Result<Option<TaskDuration>, String> {
+        //! Duration from e.g. 30m, 2h or 1d, None if the text is empty
+        let text = text.trim();
+        let Some(last) = text.chars().last() else {
+            return Ok(None);
+        };
+        let amount = text[..text.len() - last.len_utf8()].trim().parse::<u32>();
+        let duration = match (amount, last) {
+            (Ok(amount @ 1..), 'm') => Some(TaskDuration::minutes(amount)),
+            // None if there are too many hours to count in minutes
+            (Ok(amount @ 1..), 'h') => amount.checked_mul(60).map(TaskDuration::minutes),
+            (Ok(amount @ 1..), 'd') => Some(TaskDuration {
+                amount,
+                unit: String::from("day"),
+            }),
+            _ => None,
+        };
+        match duration {
+            Some(duration) => Ok(Some(duration)),
+            None => Err(format!(
+                "duration must be like 30m, 2h or 1d, not \"{text}\""
+            )),
+        }
+    }
+
+    fn minutes(amount: u32) -> TaskDuration {
+        TaskDuration {
+            amount,
+            unit: String::from("minute"),
+        }
+    }
+
+    fn edit_string(&self) -> String {
+        //! In the form TaskDuration::parse reads
+        match self.unit.as_str() {
+            "day" => format!("{}d", self.amount),
+            _ if self.amount.is_multiple_of(60) => format!("{}h", self.amount / 60),
+            _ => format!("{}m", self.amount),
+        }
+    }
+}
+
+impl Deadline {
+    pub fn parse(text: &str) -> Result<Option<Deadline>, String> {
+        //! Deadline from a YYYY-MM-DD date, None if the text is empty
+        let text = text.trim();
+        if text.is_empty() {
+            return Ok(None);
+        }
+        match chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+            Ok(_) => Ok(Some(Deadline {
+                date: text.to_string(),
+            })),
+            Err(_) => Err(format!(
+                "deadline must be a date like 2024-05-01, not \"{text}\""
+            )),
+        }
+    }
 }
 
+impl Due {
+    fn args(&self) -> DueArgs {
+        //! The exact date along with the string it was set with
+        DueArgs {
+            date: Some(self.date.clone()).filter(|date| !date.is_empty()),
+            string: Some(self.string.clone()).filter(|string| !string.is_empty()),
+            lang: self.lang.clone(),
+        }
+    }
+
+    fn edit_string(&self) -> &str {
+        //! What edit mode shows to be changed, the natural language string if there is one
+        match self.string.is_empty() {
+            true => &self.date,
+            false => &self.string,
+        }
+    }
+
+    fn short(&self, format: &DueFormat) -> String {
+        //! Date as shown in the task list, the raw date if it can't be parsed
+        match DueDate::parse(&self.date) {
+            Some(date) => date.format(format),
+            None => self.date.clone(),
+        }
+    }
+
+    fn describe(&self, format: &DueFormat) -> String {
+        //! Date, the string it was set with and the raw date the API sent
+        let recurring = match self.is_recurring {
+            true => " ↻",
+            false => "",
+        };
+        let mut raw = self.date.clone();
+        if let Some(timezone) = &self.timezone {
+            raw.push_str(&format!(" {timezone}"));
+        }
+        match (self.date.as_str(), self.string.as_str()) {
+            ("", string) => format!("{string} (waiting to sync)"),
+            (_, "") => format!("{}\n({raw})", self.short(format)),
+            (_, string) => format!("{} - {string}{recurring}\n({raw})", self.short(format)),
+        }
+    }
+}
+
+/// Resources requested from the sync endpoint
+const RESOURCE_TYPES: &str =
+    "[\"items\", \"projects\", \"sections\", \"labels\", \"notes\", \"reminders\", \"user\", \"collaborators\"]";
+
 #[derive(Clone, Serialize, Deserialize)]
+/// Represents json returned from a sync request, with every synced resource
+pub struct SyncData {
+    pub full_sync: bool,
+    pub sync_token: String,
+    #[serde(default)]
+    pub items: Vec<Task>,
+    #[serde(default)]
+    pub projects: Vec<Project>,
+    #[serde(default)]
+    pub sections: Vec<Section>,
+    #[serde(default)]
+    pub labels: Vec<Label>,
+    #[serde(default)]
+    pub notes: Vec<Note>,
+    #[serde(default)]
+    pub reminders: Vec<Reminder>,
+    /// only sent when it has changed
+    #[serde(default)]
+    pub user: Option<User>,
+    #[serde(default)]
+    pub collaborators: Vec<Collaborator>,
+}
+
+#[derive(Clone, Deserialize)]
 /// Represents json returned from a write request
 struct WriteResponse {
     sync_token: String,
-    sync_status: std::collections::HashMap<String, String>,
+    sync_status: HashMap<String, CommandStatus>,
+    /// ids the server gave to anything added with a temp_id
+    #[serde(default)]
+    temp_id_mapping: HashMap<String, String>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+/// Outcome of a single command, the string "ok" or an error object
+enum CommandStatus {
+    Ok(String),
+    Error(CommandError),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+/// Why the server rejected a command
+pub struct CommandError {
+    pub error_code: i64,
+    pub error: String,
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (error {})", self.error, self.error_code)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+/// A single command for the sync endpoint, serialized as
+/// {"type": ..., "args": {...}, "uuid": ..., "temp_id": ...}
+pub struct SyncCommand {
+    #[serde(flatten)]
+    command: Command,
+    uuid: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    temp_id: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "args", rename_all = "snake_case")]
+/// The type of a sync command along with its arguments
+pub enum Command {
+    /// Adds a task, to the inbox unless a project, section or parent is given.
+    /// The content is used as it is without parsing
+    ItemAdd {
+        content: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        description: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        labels: Vec<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        priority: Option<u8>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        due: Option<DueArgs>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        duration: Option<TaskDuration>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        deadline: Option<Deadline>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        project_id: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        section_id: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        parent_id: Option<String>,
+    },
+    /// Also deletes every sub-task
+    ItemDelete {
+        id: String,
+    },
+    ItemClose {
+        id: String,
+    },
+    ItemUncomplete {
+        id: String,
+    },
+    ItemUpdate {
+        id: String,
+        content: String,
+        description: String,
+        labels: Vec<String>,
+        priority: u8,
+        /// left out to keep the due date as it is, null to remove it
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            deserialize_with = "deserialize_some"
+        )]
+        due: Option<Option<DueArgs>>,
+        /// null removes it
+        duration: Option<TaskDuration>,
+        /// null removes it
+        deadline: Option<Deadline>,
+    },
+    /// Moves a task under a parent task, or to the top of a section or project.
+    /// Only one of the destinations may be given
+    ItemMove {
+        id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        parent_id: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        section_id: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        project_id: Option<String>,
+    },
+    ProjectAdd {
+        name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        color: Option<String>,
+    },
+    ProjectUpdate {
+        id: String,
+        name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        color: Option<String>,
+    },
+    ProjectArchive {
+        id: String,
+    },
+    /// Also deletes every task and section in the project
+    ProjectDelete {
+        id: String,
+    },
+    SectionAdd {
+        name: String,
+        project_id: String,
+    },
+    SectionUpdate {
+        id: String,
+        name: String,
+    },
+    SectionArchive {
+        id: String,
+    },
+    SectionDelete {
+        id: String,
+    },
+    LabelAdd {
+        name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        color: Option<String>,
+    },
+    /// Renaming a personal label renames it on every task that has it
+    LabelUpdate {
+        id: String,
+        name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        color: Option<String>,
+    },
+    LabelDelete {
+        id: String,
+    },
+    NoteAdd {
+        item_id: String,
+        content: String,
+    },
+    NoteUpdate {
+        id: String,
+        content: String,
+    },
+    NoteDelete {
+        id: String,
+    },
+    ReminderAdd {
+        item_id: String,
+        #[serde(rename = "type")]
+        kind: ReminderKind,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        due: Option<DueArgs>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        minute_offset: Option<i64>,
+    },
+    ReminderDelete {
+        id: String,
+    },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReminderKind {
+    /// goes off at the due date given
+    Absolute,
+    /// goes off minute_offset minutes before the task is due
+    Relative,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+/// Due date as sent in command arguments, either an exact date or a
+/// natural language string for the server to parse
+pub struct DueArgs {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    string: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lang: Option<String>,
+}
+
+impl DueArgs {
+    pub fn date(date: String) -> DueArgs {
+        DueArgs {
+            date: Some(date),
+            string: None,
+            lang: None,
+        }
+    }
+}
+
+fn deserialize_some<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+    deserializer: D,
+) -> Result<Option<T>, D::Error> {
+    //! Reads a field that is present as Some, even if it is null, so a missing
+    //! Option<Option<_>> field and a null one can be told apart
+    T::deserialize(deserializer).map(Some)
+}
+
+impl SyncCommand {
+    pub fn new(command: Command) -> SyncCommand {
+        //! Wraps a command with a fresh uuid, used by the server to deduplicate retries
+        SyncCommand {
+            command,
+            uuid: uuid::Uuid::new_v4().to_string(),
+            temp_id: None,
+        }
+    }
+
+    pub fn with_temp_id(mut self, temp_id: String) -> SyncCommand {
+        //! Id other commands can use for what this command adds, until the server gives it a real one
+        self.temp_id = Some(temp_id);
+        self
+    }
+
+    pub fn uuid(&self) -> &str {
+        &self.uuid
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.command.name()
+    }
+
+    pub fn remap(&mut self, temp_id_mapping: &HashMap<String, String>) {
+        //! Swaps any temp ids in the arguments for the real ids the server gave them
+        let mut value = serde_json::to_value(&self.command).expect("commands always serialize");
+        if let Some(args) = value.get_mut("args").and_then(|args| args.as_object_mut()) {
+            for (key, id) in args.iter_mut() {
+                let real_id = id.as_str().and_then(|id| temp_id_mapping.get(id));
+                if let (true, Some(real_id)) = (key == "id" || key.ends_with("_id"), real_id) {
+                    *id = serde_json::Value::from(real_id.clone());
+                }
+            }
+        }
+        self.command = serde_json::from_value(value).expect("remapped commands still deserialize");
+    }
+}
+
+impl Command {
+    pub fn name(&self) -> &'static str {
+        //! The command type as the sync endpoint names it
+        match self {
+            Command::ItemAdd { .. } => "item_add",
+            Command::ItemDelete { .. } => "item_delete",
+            Command::ItemClose { .. } => "item_close",
+            Command::ItemUncomplete { .. } => "item_uncomplete",
+            Command::ItemUpdate { .. } => "item_update",
+            Command::ItemMove { .. } => "item_move",
+            Command::ProjectAdd { .. } => "project_add",
+            Command::ProjectUpdate { .. } => "project_update",
+            Command::ProjectArchive { .. } => "project_archive",
+            Command::ProjectDelete { .. } => "project_delete",
+            Command::SectionAdd { .. } => "section_add",
+            Command::SectionUpdate { .. } => "section_update",
+            Command::SectionArchive { .. } => "section_archive",
+            Command::SectionDelete { .. } => "section_delete",
+            Command::LabelAdd { .. } => "label_add",
+            Command::LabelUpdate { .. } => "label_update",
+            Command::LabelDelete { .. } => "label_delete",
+            Command::NoteAdd { .. } => "note_add",
+            Command::NoteUpdate { .. } => "note_update",
+            Command::NoteDelete { .. } => "note_delete",
+            Command::ReminderAdd { .. } => "reminder_add",
+            Command::ReminderDelete { .. } => "reminder_delete",
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+/// Builder collecting several commands to be sent in one sync request
+/// ```
+/// let result = CommandBatch::new()
+///     .with(Command::ItemClose { id })
+///     .with(Command::ItemUpdate { .. })
+///     .send(&api)?;
+/// ```
+pub struct CommandBatch {
+    commands: Vec<SyncCommand>,
+}
+
+impl CommandBatch {
+    pub fn new() -> CommandBatch {
+        CommandBatch::default()
+    }
+
+    pub fn push(&mut self, command: Command) {
+        self.commands.push(SyncCommand::new(command));
+    }
+
+    pub fn with(mut self, command: Command) -> CommandBatch {
+        //! Builder style push
+        self.push(command);
+        self
+    }
+
+    pub fn send(self, client: &Api) -> Result<BatchResult, ApiError> {
+        //! Sends every command in one request, the batch is applied in order by the server
+        let response = client.post_commands(&self.commands)?;
+        let mut statuses = response.sync_status;
+        let results = self
+            .commands
+            .into_iter()
+            .map(|command| {
+                let status = match statuses.remove(&command.uuid) {
+                    Some(CommandStatus::Error(error)) => Err(error),
+                    Some(CommandStatus::Ok(status)) if status == "ok" => Ok(()),
+                    Some(CommandStatus::Ok(status)) => Err(CommandError {
+                        error_code: 0,
+                        error: status,
+                    }),
+                    None => Err(CommandError {
+                        error_code: 0,
+                        error: String::from("no status returned for command"),
+                    }),
+                };
+                (command, status)
+            })
+            .collect();
+        Ok(BatchResult {
+            sync_token: response.sync_token,
+            temp_id_mapping: response.temp_id_mapping,
+            results,
+        })
+    }
+}
+
+/// Per command outcome of a sent CommandBatch
+pub struct BatchResult {
+    pub sync_token: String,
+    pub temp_id_mapping: HashMap<String, String>,
+    results: Vec<(SyncCommand, Result<(), CommandError>)>,
+}
+
+impl BatchResult {
+    pub fn failures(&self) -> Vec<(&SyncCommand, &CommandError)> {
+        //! Every command the server rejected, in the order they were sent
+        self.results
+            .iter()
+            .filter_map(|(command, status)| status.as_ref().err().map(|error| (command, error)))
+            .collect()
+    }
+
+    pub fn into_result(self, request: &str) -> Result<String, ApiError> {
+        //! Turns any rejected commands into an ApiError, otherwise gives the new sync token
+        let failures: Vec<(String, CommandError)> = self
+            .failures()
+            .into_iter()
+            .map(|(command, error)| (command.name().to_string(), error.clone()))
+            .collect();
+        if failures.is_empty() {
+            Ok(self.sync_token)
+        } else {
+            Err(ApiError::Rejected {
+                request: request.to_string(),
+                failures,
+            })
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+/// How requests that fail with a network error, a 5xx or a 429 are retried.
+/// Delays double each attempt from base_delay_ms up to max_delay_ms, with jitter,
+/// and a Retry-After header on a 429 is used as the delay instead, also capped
+/// at max_delay_ms so one response can't hold up every other request for long
+pub struct RetryPolicy {
+    /// total attempts including the first, 1 disables retrying
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 5,
+            base_delay_ms: 500,
+            max_delay_ms: 30_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn delay(&self, attempt: u32) -> Duration {
+        //! Backoff before the given retry (1 for the first), picked at random
+        //! between half and all of the exponential delay so clients spread out
+        let exponential = self
+            .base_delay_ms
+            .saturating_mul(1u64.checked_shl(attempt - 1).unwrap_or(u64::MAX))
+            .min(self.max_delay_ms);
+        let half = exponential / 2;
+        let jitter = (uuid::Uuid::new_v4().as_u128() % (half as u128 + 1)) as u64;
+        Duration::from_millis(half + jitter)
+    }
 }
 
 /// API client struct
 pub struct Api {
     token: String,
+    base_url: String,
+    retry_policy: RetryPolicy,
     client: reqwest::blocking::Client,
 }
 
 impl Api {
-    pub fn new(token: String) -> Api {
-        //! Create new API client struct. Consumes an API auth token as a String
+    pub fn new(token: String, base_url: String) -> Api {
+        //! Create new API client struct. Consumes an API auth token as a String,
+        //! and the base url all endpoints are relative to (normally config::DEFAULT_API_URL)
         Api {
             token,
+            base_url,
+            retry_policy: RetryPolicy::default(),
             client: reqwest::blocking::Client::new(),
         }
     }
 
-    fn post(&self, url: String, form_fields: &[(String, String)]) -> Result<String, u16> {
-        let response = match self
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Api {
+        //! Replace the default retry policy
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{path}", self.base_url)
+    }
+
+    fn sync_request(&self) -> String {
+        //! Describes a sync request the way ApiError does
+        format!("POST {}", self.url("sync"))
+    }
+
+    fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        form_fields: &[(String, String)],
+    ) -> Result<T, ApiError> {
+        //! POST a form encoded body, used by the sync endpoint
+        let url = self.url(path);
+        let request = format!("POST {url}");
+        let builder = self
             .client
             .post(url)
             .form(form_fields)
-            .header(AUTHORIZATION, format!("Bearer {}", self.token))
-            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
-            .send()
-        {
-            Ok(res) => res,
-            Err(_) => return Err(3),
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded");
+        self.send(request, builder)
+    }
+
+    fn post_json<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &serde_json::Value,
+        request_id: &str,
+    ) -> Result<T, ApiError> {
+        //! POST a json body, used by the REST style endpoints. These aren't idempotent,
+        //! so every attempt carries the same request id for the server to spot repeats
+        let url = self.url(path);
+        let request = format!("POST {url}");
+        let builder = self
+            .client
+            .post(url)
+            .body(body.to_string())
+            .header(CONTENT_TYPE, "application/json")
+            .header("X-Request-Id", request_id);
+        self.send(request, builder)
+    }
+
+    fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(String, String)],
+    ) -> Result<T, ApiError> {
+        //! GET with query parameters, used by the REST style endpoints
+        let url = self.url(path);
+        let request = format!("GET {url}");
+        let builder = self.client.get(url).query(query);
+        self.send(request, builder)
+    }
+
+    fn get_pages<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(String, String)],
+    ) -> Result<Vec<T>, ApiError> {
+        //! GET every page of an endpoint paged with a cursor, following next_cursor
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let mut query = query.to_vec();
+            if let Some(cursor) = cursor {
+                query.push((String::from("cursor"), cursor));
+            }
+            let page: Page<T> = self.get(path, &query)?;
+            items.extend(page.items);
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => return Ok(items),
+            }
+        }
+    }
+
+    fn send<T: DeserializeOwned>(
+        &self,
+        request: String,
+        builder: reqwest::blocking::RequestBuilder,
+    ) -> Result<T, ApiError> {
+        //! Sends the request, retrying according to the retry policy, and parses the response
+        let builder = builder.header(AUTHORIZATION, format!("Bearer {}", self.token));
+        let mut attempt = 1;
+        let body = loop {
+            // bodies here are always in memory so cloning never fails
+            let this_attempt = builder.try_clone().expect("request body is not a stream");
+            let (error, retry_after) = match send_once(&request, this_attempt) {
+                Ok(body) => break body,
+                Err(failure) => failure,
+            };
+            let retryable = matches!(error, ApiError::Network { .. })
+                || error.is_server_error()
+                || error.is_rate_limited();
+            if !retryable || attempt >= self.retry_policy.max_attempts {
+                return Err(error);
+            }
+            let delay = match retry_after {
+                Some(delay) => delay.min(Duration::from_millis(self.retry_policy.max_delay_ms)),
+                None => self.retry_policy.delay(attempt),
+            };
+            thread::sleep(delay);
+            attempt += 1;
         };
-        match response.status().as_u16() {
-            200 => Ok(response.text().unwrap()),
-            x => Err(x),
+        match serde_json::from_str::<T>(&body) {
+            Ok(parsed) => Ok(parsed),
+            Err(source) => Err(ApiError::Parse {
+                request,
+                body,
+                source,
+            }),
         }
     }
 
-    pub fn get_tasks(&self, sync_token: &str) -> Result<(Vec<Task>, String), u16> {
-        //! Get a vector of all tasks and an updated sync token
-        let url = "https://api.todoist.com/sync/v9/sync".to_string();
-        let result = self.post(
-            url,
+    pub fn sync(&self, sync_token: &str) -> Result<SyncData, ApiError> {
+        //! Get every resource changed since the sync token ("*" for everything),
+        //! along with an updated sync token
+        self.post(
+            "sync",
             &[
                 (String::from("sync_token"), String::from(sync_token)),
-                (String::from("resource_types"), String::from("[\"items\"]")),
+                (String::from("resource_types"), String::from(RESOURCE_TYPES)),
             ],
-        );
-        let syncresponse = match serde_json::from_str::<SyncResponse>(&result?) {
-            Ok(syncresponse) => syncresponse,
-            Err(_) => return Err(2),
-        };
-        let mut task_list = syncresponse.items;
-        task_list.sort_by_key(|task| task.priority);
-        task_list.reverse();
-
-        Ok((task_list, syncresponse.sync_token))
-    }
-
-    pub fn complete_task(&self, task: &Task) -> Result<String, u16> {
-        //! Mark task as complete based on Task object, returning the new sync token
-        let todoist_command = format!(
-            "[{{
-                \"type\": \"item_close\", 
-                \"uuid\": \"{}\", 
-                \"args\": {{
-                    \"id\": \"{}\" 
-                }}
-            }}]",
-            uuid::Uuid::new_v4(),
-            task.id
-        );
-        let url = "https://api.todoist.com/sync/v9/sync".to_string();
-        let result = self.post(url, &[(String::from("commands"), todoist_command)]);
-        let writeresponse = match serde_json::from_str::<WriteResponse>(&result?) {
-            Ok(syncresponse) => syncresponse,
-            Err(_) => return Err(2),
-        };
-        Ok(writeresponse.sync_token)
+        )
     }
 
-    pub fn quick_add(&self, quick: String) -> Result<Task, u16> {
-        //! Create a new task using the quick add method, allowing for shorthand for due date, label, and priority, returning the added task
-        let url = "https://api.todoist.com/sync/v9/quick/add".to_string();
-        let result = self.post(url, &[(String::from("text"), quick)]);
-        let writeresponse = match serde_json::from_str::<Task>(&result?) {
-            Ok(syncresponse) => syncresponse,
-            Err(_) => return Err(2),
-        };
-        Ok(writeresponse)
-    }
-
-    pub fn edit(&self, task: Task) -> Result<String, u16> {
-        //! Mark task as complete based on Task object, returning the new sync token
-        let todoist_command = format!(
-            "[{{
-                \"type\": \"item_update\", 
-                \"uuid\": \"{}\", 
-                \"args\": {{
-                    \"id\": \"{}\" ,
-                    \"content\": \"{}\",
-                    \"description\": \"{}\" ,
-                    \"labels\": [{}],
-                    \"priority\": \"{}\"
-                    {}
-                }}
-            }}]",
-            uuid::Uuid::new_v4(),
-            task.id,
-            task.content,
-            task.description,
-            task.labels
-                .iter()
-                .map(|x| format!("\"{x}\""))
-                .collect::<Vec<String>>()
-                .join(","),
-            task.priority,
-            match task.due {
-                Some(due) => format!(
-                    "
-                        ,\"due\": {{
-                            \"date\": \"{}\"
-                        }}
-                    ",
-                    due.date
-                ),
-                None => String::new(),
-            }
-        );
-        let url = "https://api.todoist.com/sync/v9/sync".to_string();
-        let result = self.post(url, &[(String::from("commands"), todoist_command)]);
-        let writeresponse = match serde_json::from_str::<WriteResponse>(&result?) {
-            Ok(syncresponse) => syncresponse,
-            Err(_) => return Err(2),
-        };
-        Ok(writeresponse.sync_token)
+    fn post_commands(&self, commands: &[SyncCommand]) -> Result<WriteResponse, ApiError> {
+        //! Send sync commands as a json array to the sync endpoint
+        let commands = serde_json::to_string(commands).expect("sync commands always serialize");
+        self.post("sync", &[(String::from("commands"), commands)])
+    }
+
+    pub fn send_batch(&self, batch: CommandBatch) -> Result<String, ApiError> {
+        //! Send a batch of commands, returning the new sync token, or an
+        //! ApiError::Rejected if any of the commands failed
+        batch.send(self)?.into_result(&self.sync_request())
+    }
+
+    pub fn quick_add(&self, quick: String, request_id: &str) -> Result<Task, ApiError> {
+        //! Create a new task using the quick add method, allowing for shorthand for due date, label, and priority, returning the added task.
+        //! Sending again with the same request id won't add the task twice
+        self.post_json(
+            "tasks/quick",
+            &serde_json::json!({ "text": quick }),
+            request_id,
+        )
+    }
+
+    pub fn completed_tasks(
+        &self,
+        since: &str,
+        until: &str,
+    ) -> Result<Vec<CompletedTask>, ApiError> {
+        //! Tasks completed between two UTC times, which can be at most 3 months apart
+        self.get_pages(
+            "tasks/completed/by_completion_date",
+            &[
+                (String::from("since"), String::from(since)),
+                (String::from("until"), String::from(until)),
+                (String::from("limit"), String::from("200")),
+            ],
+        )
+    }
+
+    pub fn replay(&self, commands: Vec<SyncCommand>) -> Result<BatchResult, ApiError> {
+        //! Send commands that were queued up, keeping their uuids so any the server
+        //! already applied from an earlier attempt aren't applied twice
+        CommandBatch { commands }.send(self)
+    }
+}
+
+fn send_once(
+    request: &str,
+    builder: reqwest::blocking::RequestBuilder,
+) -> Result<String, (ApiError, Option<Duration>)> {
+    //! Makes a single attempt at a request, returning the body of a 200 response.
+    //! On failure also gives the Retry-After delay if the server sent one
+    let request = request.to_string();
+    let response = match builder.send() {
+        Ok(res) => res,
+        Err(source) => return Err((ApiError::Network { request, source }, None)),
+    };
+    let status = response.status().as_u16();
+    let retry_after = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs);
+    let body = match response.text() {
+        Ok(body) => body,
+        Err(source) => return Err((ApiError::Network { request, source }, None)),
+    };
+    if status != 200 {
+        return Err((
+            ApiError::Http {
+                request,
+                status,
+                body,
+            },
+            retry_after,
+        ));
     }
+    Ok(body)
 }
 
 impl Task {
-    pub fn create_task_obj(
-        id: String,
-        content: String,
-        description: String,
-        date: String,
-        labels: Vec<String>,
-        priority: u8,
-    ) -> Task {
+    pub fn pending(temp_id: String, content: String, project_id: String) -> Task {
+        //! Task added locally that the server hasn't seen yet, with a temp id until it has
         Task {
-            id,
+            id: temp_id,
             content,
-            description,
+            description: String::new(),
+            checked: false,
+            is_deleted: false,
+            labels: Vec::new(),
+            priority: 1,
+            due: None,
+            project_id,
+            section_id: None,
+            parent_id: None,
+            child_order: 0,
+            duration: None,
+            deadline: None,
+        }
+    }
+
+    pub fn update_command(&self) -> Command {
+        //! item_update setting the task's fields on the server
+        // only strings typed in edit mode are sent, resending an unchanged
+        // recurring string would move it to the next occurrence from today
+        let due = match &self.due {
+            None => Some(None),
+            Some(due) if due.date.is_empty() => Some(Some(DueArgs {
+                date: None,
+                string: Some(due.string.clone()),
+                lang: due.lang.clone(),
+            })),
+            Some(_) => None,
+        };
+        self.item_update(due)
+    }
+
+    pub fn restore_command(&self) -> Command {
+        //! item_update putting every field back to how it is in this copy of the task,
+        //! sending the exact date so a recurring task returns to this occurrence
+        self.item_update(Some(self.due.as_ref().map(Due::args)))
+    }
+
+    pub fn add_command(&self) -> Command {
+        //! item_add creating a task like this one, where this one is
+        Command::ItemAdd {
+            content: self.content.clone(),
+            description: self.description.clone(),
+            labels: self.labels.clone(),
+            priority: Some(self.priority),
+            due: self.due.as_ref().map(Due::args),
+            duration: self.duration.clone(),
+            deadline: self.deadline.clone(),
+            project_id: Some(self.project_id.clone()).filter(|id| !id.is_empty()),
+            section_id: self.section_id.clone(),
+            parent_id: self.parent_id.clone(),
+        }
+    }
+
+    pub fn uncompleted(&self) -> Task {
+        Task {
             checked: false,
-            labels,
-            priority,
-            due: Some(Due {
-                date,
-                string: String::new(),
+            ..self.clone()
+        }
+    }
+
+    pub fn duplicate(&self, temp_id: String) -> Task {
+        //! Copy of the task under a temp id, until the server has added it
+        Task {
+            id: temp_id,
+            ..self.clone()
+        }
+    }
+
+    fn item_update(&self, due: Option<Option<DueArgs>>) -> Command {
+        Command::ItemUpdate {
+            id: self.id.clone(),
+            content: self.content.clone(),
+            description: self.description.clone(),
+            labels: self.labels.clone(),
+            priority: self.priority,
+            due,
+            duration: self.duration.clone(),
+            deadline: self.deadline.clone(),
+        }
+    }
+
+    pub fn remap(&mut self, temp_id_mapping: &HashMap<String, String>) {
+        //! Swaps temp ids for the real ids the server gave them
+        for id in [Some(&mut self.id), self.parent_id.as_mut()]
+            .into_iter()
+            .flatten()
+        {
+            if let Some(real_id) = temp_id_mapping.get(id.as_str()) {
+                *id = real_id.clone();
+            }
+        }
+    }
+
+    pub fn set_parent_id(&mut self, parent_id: Option<String>) {
+        self.parent_id = parent_id;
+    }
+
+    pub fn set_place(&mut self, project_id: String, section_id: Option<String>) {
+        //! Moves the task to a project, and a section of it or no section
+        self.project_id = project_id;
+        self.section_id = section_id;
+    }
+
+    pub fn edited(&self, edit: TaskEdit) -> Task {
+        //! Copy of the task with the fields from edit mode replaced. A changed due
+        //! string is kept without a date until the server has parsed it
+        let due_string = edit.due_string.trim();
+        let due = match &self.due {
+            _ if due_string.is_empty() => None,
+            Some(due) if due.edit_string() == due_string => Some(due.clone()),
+            due => Some(Due {
+                date: String::new(),
+                string: due_string.to_string(),
+                is_recurring: false,
+                timezone: None,
+                lang: due.as_ref().and_then(|due| due.lang.clone()),
             }),
+        };
+        Task {
+            content: edit.content,
+            description: edit.description,
+            labels: edit.labels,
+            priority: edit.priority,
+            due,
+            duration: edit.duration,
+            deadline: edit.deadline,
+            ..self.clone()
         }
     }
 
-    pub fn to_info_string(&self) -> String {
+    pub fn to_info_string(&self, format: &DueFormat) -> String {
         //! Produce a string suitable for the infomation pane based on a task object
         format!(
-            "!!{} - {}\n@{}\n\n{}\n\n---\n{}",
+            "!!{} - {}\n@{}\n\n{}\n\n---\n{}{}{}",
             self.priority,
             self.content,
             self.labels.join(","),
             self.description,
             match &self.due {
                 None => String::from("not due"),
-                Some(x) => x.date.to_owned(),
+                Some(x) => x.describe(format),
+            },
+            match &self.duration {
+                None => String::new(),
+                Some(x) if x.unit == "day" => format!("\ntakes {} day(s)", x.amount),
+                Some(x) => format!("\ntakes {} minutes", x.amount),
+            },
+            match &self.deadline {
+                None => String::new(),
+                Some(x) => match DueDate::parse(&x.date) {
+                    Some(date) => format!("\ndeadline {}", date.format(format)),
+                    None => format!("\ndeadline {}", x.date),
+                },
             },
         )
     }
 
-    pub fn to_list_string(&self, width: u16) -> String {
-        //! Produce a string suitable for the task list based on a task object
+    pub fn to_list_string(&self, width: u16, prefix: &str, format: &DueFormat) -> String {
+        //! Produce a string suitable for the task list based on a task object,
+        //! with the prefix (e.g. indentation) put before the content
         let content_length = (width as f32 * 0.6).round() as usize;
-        let spacer_length = match usize::overflowing_sub(width as usize, content_length + 17) {
+        let spacer_length = match usize::overflowing_sub(width as usize, content_length + 21) {
             (length, overflow) if !overflow => length,
             _ => 2,
         };
 
         format!(
-            "{:content_length$}{:spacer_length$}{:10}  {:1}",
-            self.content
+            "{:content_length$}{:spacer_length$}{:14}{:1} {:1}",
+            format!("{prefix}{}", self.content)
                 .chars()
                 .take(content_length)
                 .collect::<String>(),
             " ",
             match &self.due {
                 None => String::from("not due"),
-                Some(x) => x.date.to_owned(),
+                // not parsed yet, show what was typed
+                Some(x) if x.date.is_empty() => x.string.chars().take(14).collect(),
+                Some(x) => x.short(format),
+            },
+            match self.is_recurring() {
+                true => "↻",
+                false => " ",
             },
             self.priority,
         )
@@ -238,7 +1177,7 @@ impl Task {
             self.labels.clone(),
             match &self.due {
                 None => String::new(),
-                Some(x) => x.date.clone(),
+                Some(x) => x.edit_string().to_string(),
             },
             self.priority,
         )
@@ -247,4 +1186,546 @@ impl Task {
     pub fn get_id(&self) -> String {
         self.id.clone()
     }
+
+    pub fn is_recurring(&self) -> bool {
+        self.due.as_ref().is_some_and(|due| due.is_recurring)
+    }
+
+    pub fn duration_string(&self) -> String {
+        //! Duration as edit mode shows it, empty if there isn't one
+        self.duration
+            .as_ref()
+            .map(|x| x.edit_string())
+            .unwrap_or_default()
+    }
+
+    pub fn deadline_date(&self) -> Option<&str> {
+        self.deadline.as_ref().map(|x| x.date.as_str())
+    }
+
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    pub fn project_id(&self) -> &str {
+        &self.project_id
+    }
+
+    pub fn section_id(&self) -> Option<&str> {
+        self.section_id.as_deref()
+    }
+
+    pub fn parent_id(&self) -> Option<&str> {
+        self.parent_id.as_deref()
+    }
+
+    pub fn child_order(&self) -> i64 {
+        self.child_order
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+}
+
+impl Resource for Task {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn is_active(&self) -> bool {
+        !(self.checked || self.is_deleted)
+    }
+}
+
+pub fn sort_tasks_by_deadline(tasks: &mut [Task]) {
+    //! Stable sort putting the earliest deadlines first and tasks without one last
+    tasks.sort_by(|a, b| {
+        (a.deadline.is_none(), a.deadline_date()).cmp(&(b.deadline.is_none(), b.deadline_date()))
+    });
+}
+
+pub fn sort_tasks(tasks: &mut [Task]) {
+    //! Sorts tasks into the order they are displayed, highest priority first
+    tasks.sort_by_key(|task| std::cmp::Reverse(task.priority));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::mock::{MockServer, Response};
+    use proptest::prelude::*;
+
+    fn api(server: &MockServer) -> Api {
+        Api::new(String::from("token"), server.url.clone()).with_retry_policy(RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        })
+    }
+
+    fn task(id: &str) -> Task {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "content": "buy milk",
+            "description": "semi skimmed",
+            "checked": false,
+            "labels": ["shop"],
+            "priority": 4,
+            "due": {"date": "2024-05-01", "string": "every wednesday", "is_recurring": true},
+            "project_id": "inbox",
+            "duration": {"amount": 15, "unit": "minute"},
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn sync_posts_form_with_token_and_resource_types() {
+        let server = MockServer::scripted(vec![Response::json(
+            r#"{"full_sync": false, "sync_token": "next"}"#,
+        )]);
+        let data = api(&server).sync("previous").unwrap();
+        assert_eq!(data.sync_token, "next");
+
+        let requests = server.requests();
+        let request = &requests[0];
+        assert_eq!(
+            (request.method.as_str(), request.path.as_str()),
+            ("POST", "/sync")
+        );
+        assert_eq!(request.header("authorization"), Some("Bearer token"));
+        assert_eq!(
+            request.header("content-type"),
+            Some("application/x-www-form-urlencoded")
+        );
+        assert_eq!(request.field("sync_token").as_deref(), Some("previous"));
+        let resource_types: Vec<String> =
+            serde_json::from_str(&request.field("resource_types").unwrap()).unwrap();
+        assert!(resource_types.contains(&String::from("items")));
+        assert!(resource_types.contains(&String::from("collaborators")));
+    }
+
+    #[test]
+    fn sync_data_parses_every_resource() {
+        let body = serde_json::json!({
+            "full_sync": true,
+            "sync_token": "abc",
+            "items": [serde_json::to_value(task("1")).unwrap()],
+            "projects": [{"id": "inbox", "name": "Inbox", "parent_id": null, "inbox_project": true}],
+            "sections": [{"id": "s1", "name": "Later", "project_id": "inbox"}],
+            "labels": [{"id": "l1", "name": "shop"}],
+            "notes": [{"id": "n1", "item_id": "1", "content": "oat milk?"}],
+            "reminders": [{"id": "r1", "item_id": "1", "type": "relative", "minute_offset": 30}],
+            "user": {"id": "u1", "full_name": "Someone"},
+            "collaborators": [{"id": "u2"}],
+            "day_orders": {},
+        });
+        let server = MockServer::scripted(vec![Response::json(body)]);
+        let data = api(&server).sync("*").unwrap();
+        assert!(data.full_sync);
+        assert_eq!(data.items[0].content(), "buy milk");
+        assert!(data.items[0].is_recurring());
+        assert_eq!(data.items[0].duration_string(), "15m");
+        assert!(data.projects[0].is_inbox);
+        assert_eq!(data.sections[0].project_id, "inbox");
+        assert_eq!(data.labels[0].name, "shop");
+        assert_eq!(data.notes[0].item_id, "1");
+        assert_eq!(data.reminders[0].minute_offset, Some(30));
+        assert_eq!(data.user.unwrap().id, "u1");
+        assert_eq!(data.collaborators[0].id, "u2");
+    }
+
+    #[test]
+    fn sync_reports_unparseable_body() {
+        let server = MockServer::scripted(vec![Response::json(r#"{"items": []}"#)]);
+        let result = api(&server).sync("*");
+        assert!(matches!(result, Err(ApiError::Parse { .. })));
+    }
+
+    #[test]
+    fn item_add_is_sent_with_its_temp_id() {
+        let mut copy = task("1").duplicate(String::from("temp"));
+        copy.set_place(String::from("work"), Some(String::from("s1")));
+        let command = SyncCommand::new(copy.add_command()).with_temp_id(String::from("temp"));
+        let uuid = command.uuid().to_string();
+        let server = MockServer::scripted(vec![Response::json(serde_json::json!({
+            "sync_token": "next",
+            "sync_status": {uuid.clone(): "ok"},
+            "temp_id_mapping": {"temp": "42"},
+        }))]);
+        let result = api(&server).replay(vec![command]).unwrap();
+        assert!(result.failures().is_empty());
+        assert_eq!(result.temp_id_mapping["temp"], "42");
+
+        let commands: serde_json::Value =
+            serde_json::from_str(&server.requests()[0].field("commands").unwrap()).unwrap();
+        assert_eq!(
+            commands,
+            serde_json::json!([{
+                "type": "item_add",
+                "uuid": uuid,
+                "temp_id": "temp",
+                "args": {
+                    "content": "buy milk",
+                    "description": "semi skimmed",
+                    "labels": ["shop"],
+                    "priority": 4,
+                    "due": {"date": "2024-05-01", "string": "every wednesday"},
+                    "duration": {"amount": 15, "unit": "minute"},
+                    "project_id": "work",
+                    "section_id": "s1",
+                },
+            }])
+        );
+    }
+
+    #[test]
+    fn quick_add_posts_the_text_as_json() {
+        let server = MockServer::scripted(vec![Response::json(
+            serde_json::to_value(task("1")).unwrap(),
+        )]);
+        let added = api(&server)
+            .quick_add(String::from("buy \"milk\" p1 @shop"), "request")
+            .unwrap();
+        assert_eq!(added.id(), "1");
+
+        let request = &server.requests()[0];
+        assert_eq!(request.path, "/tasks/quick");
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        assert_eq!(request.header("x-request-id"), Some("request"));
+        let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(body, serde_json::json!({"text": "buy \"milk\" p1 @shop"}));
+    }
+
+    #[test]
+    fn rejected_commands_are_reported_by_name() {
+        let command = SyncCommand::new(Command::ItemClose {
+            id: String::from("1"),
+        });
+        let server = MockServer::scripted(vec![Response::json(serde_json::json!({
+            "sync_token": "next",
+            "sync_status": {command.uuid(): {"error_code": 22, "error": "Item not found"}},
+        }))]);
+        let batch = CommandBatch {
+            commands: vec![command],
+        };
+        match api(&server).send_batch(batch) {
+            Err(ApiError::Rejected { failures, .. }) => {
+                assert_eq!(failures[0].0, "item_close");
+                assert_eq!(failures[0].1.error_code, 22);
+            }
+            _ => panic!("expected the command to be rejected"),
+        }
+    }
+
+    #[test]
+    fn get_pages_follows_next_cursor() {
+        let completed = |id: &str| {
+            let mut task = serde_json::to_value(task(id)).unwrap();
+            task["completed_at"] = serde_json::json!("2024-05-01T09:00:00Z");
+            task
+        };
+        let server = MockServer::scripted(vec![
+            Response::json(serde_json::json!({"items": [completed("1")], "next_cursor": "page2"})),
+            Response::json(serde_json::json!({"items": [completed("2")], "next_cursor": null})),
+        ]);
+        let tasks = api(&server)
+            .completed_tasks("2024-04-25T00:00:00Z", "2024-05-02T00:00:00Z")
+            .unwrap();
+        let ids: Vec<&str> = tasks.iter().map(|x| x.task.id()).collect();
+        assert_eq!(ids, ["1", "2"]);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].path, "/tasks/completed/by_completion_date");
+        assert_eq!(requests[0].param("since"), Some("2024-04-25T00:00:00Z"));
+        assert_eq!(requests[0].param("cursor"), None);
+        assert_eq!(requests[1].param("cursor"), Some("page2"));
+        assert_eq!(requests[1].param("until"), Some("2024-05-02T00:00:00Z"));
+    }
+
+    fn tricky_text() -> impl Strategy<Value = String> {
+        //! Any unicode, heavy on the characters that break hand built json
+        prop::collection::vec(
+            prop_oneof![
+                Just('"'),
+                Just('\\'),
+                Just('\n'),
+                Just('\r'),
+                Just('\t'),
+                Just('\u{0}'),
+                Just('}'),
+                Just(','),
+                any::<char>(),
+            ],
+            0..40,
+        )
+        .prop_map(|chars| chars.into_iter().collect())
+    }
+
+    fn edited_update(content: &str, description: &str, labels: &[String]) -> SyncCommand {
+        let edited = task("1").edited(TaskEdit {
+            content: content.to_string(),
+            description: description.to_string(),
+            due_string: String::new(),
+            labels: labels.to_vec(),
+            priority: 2,
+            duration: None,
+            deadline: None,
+        });
+        SyncCommand::new(edited.update_command())
+    }
+
+    proptest! {
+        #[test]
+        fn item_update_round_trips_any_text(
+            content in tricky_text(),
+            description in tricky_text(),
+            labels in prop::collection::vec(tricky_text(), 0..4),
+        ) {
+            // json inside a form body, as post_commands sends it
+            let command = edited_update(&content, &description, &labels);
+            let body = form_urlencoded::Serializer::new(String::new())
+                .append_pair("commands", &serde_json::to_string(&[command]).unwrap())
+                .finish();
+            let (_, json) = form_urlencoded::parse(body.as_bytes()).next().unwrap();
+            let parsed: Vec<SyncCommand> = serde_json::from_str(&json).unwrap();
+            let Command::ItemUpdate {
+                content: sent,
+                description: sent_description,
+                labels: sent_labels,
+                due,
+                ..
+            } = &parsed[0].command
+            else {
+                panic!("expected an item_update");
+            };
+            prop_assert_eq!(sent, &content);
+            prop_assert_eq!(sent_description, &description);
+            prop_assert_eq!(sent_labels, &labels);
+            prop_assert!(matches!(due, Some(None)));
+        }
+    }
+
+    #[test]
+    fn item_update_text_arrives_unchanged() {
+        //! Through the form encoding as well as the json
+        let content = String::from("say \"hi\" \\ \n\"}], \"priority\": 4, {\" ✓");
+        let command = edited_update(&content, "line one\nline two", &[String::from("a\"b")]);
+        let server = MockServer::scripted(vec![Response::json(serde_json::json!({
+            "sync_token": "next",
+            "sync_status": {command.uuid(): "ok"},
+        }))]);
+        api(&server).replay(vec![command]).unwrap();
+
+        let commands: serde_json::Value =
+            serde_json::from_str(&server.requests()[0].field("commands").unwrap()).unwrap();
+        let args = &commands[0]["args"];
+        assert_eq!(args["content"], content.as_str());
+        assert_eq!(args["description"], "line one\nline two");
+        assert_eq!(args["labels"], serde_json::json!(["a\"b"]));
+        assert_eq!(args["priority"], 2);
+    }
+
+    #[test]
+    fn missing_and_null_due_are_told_apart() {
+        let parse = |args: serde_json::Value| -> Option<Option<DueArgs>> {
+            let command = serde_json::json!({"type": "item_update", "uuid": "u", "args": args});
+            match serde_json::from_value::<SyncCommand>(command)
+                .unwrap()
+                .command
+            {
+                Command::ItemUpdate { due, .. } => due,
+                _ => panic!("expected an item_update"),
+            }
+        };
+        let args = serde_json::json!({
+            "id": "1",
+            "content": "",
+            "description": "",
+            "labels": [],
+            "priority": 1,
+            "duration": null,
+            "deadline": null,
+        });
+        assert!(parse(args.clone()).is_none());
+
+        let mut removed = args.clone();
+        removed["due"] = serde_json::Value::Null;
+        assert!(matches!(parse(removed), Some(None)));
+
+        let mut set = args;
+        set["due"] = serde_json::json!({"string": "tomorrow"});
+        let due = parse(set).unwrap().unwrap();
+        assert_eq!(due.string.as_deref(), Some("tomorrow"));
+
+        // and they serialize back the way they came
+        let kept = serde_json::to_value(SyncCommand::new(task("1").item_update(None))).unwrap();
+        assert!(kept["args"].get("due").is_none());
+        let removed =
+            serde_json::to_value(SyncCommand::new(task("1").item_update(Some(None)))).unwrap();
+        assert!(removed["args"]["due"].is_null());
+    }
+
+    #[test]
+    fn remap_swaps_temp_ids_in_id_fields_only() {
+        let mapping = HashMap::from([
+            (String::from("temp"), String::from("42")),
+            (String::from("parent"), String::from("7")),
+        ]);
+        let mut command = SyncCommand::new(Command::ItemMove {
+            id: String::from("temp"),
+            parent_id: Some(String::from("parent")),
+            section_id: None,
+            project_id: None,
+        });
+        command.remap(&mapping);
+        let value = serde_json::to_value(&command).unwrap();
+        assert_eq!(
+            value["args"],
+            serde_json::json!({"id": "42", "parent_id": "7"})
+        );
+
+        // content that happens to match a temp id is left alone
+        let mut command = SyncCommand::new(Command::NoteAdd {
+            item_id: String::from("temp"),
+            content: String::from("temp"),
+        });
+        let uuid = command.uuid().to_string();
+        command.remap(&mapping);
+        let value = serde_json::to_value(&command).unwrap();
+        assert_eq!(
+            value["args"],
+            serde_json::json!({"item_id": "42", "content": "temp"})
+        );
+        assert_eq!(command.uuid(), uuid);
+    }
+
+    fn retrying_api(server: &MockServer, base_delay_ms: u64, max_delay_ms: u64) -> Api {
+        Api::new(String::from("token"), server.url.clone()).with_retry_policy(RetryPolicy {
+            max_attempts: 3,
+            base_delay_ms,
+            max_delay_ms,
+        })
+    }
+
+    fn synced() -> Response {
+        Response::json(r#"{"full_sync": true, "sync_token": "abc"}"#)
+    }
+
+    #[test]
+    fn server_errors_are_retried_until_one_succeeds() {
+        let server =
+            MockServer::scripted(vec![Response::status(503), Response::status(503), synced()]);
+        let data = retrying_api(&server, 1, 10).sync("*").unwrap();
+        assert_eq!(data.sync_token, "abc");
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn network_errors_are_retried() {
+        let mut dropped = false;
+        let server = MockServer::start(move |_| match std::mem::replace(&mut dropped, true) {
+            false => None,
+            true => Some(synced()),
+        });
+        assert!(retrying_api(&server, 1, 10).sync("*").is_ok());
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn rate_limits_wait_for_retry_after() {
+        // the backoff alone would wait a minute
+        let server = MockServer::scripted(vec![
+            Response::status(429).with_header("Retry-After", "0"),
+            synced(),
+        ]);
+        let started = std::time::Instant::now();
+        assert!(retrying_api(&server, 60_000, 60_000).sync("*").is_ok());
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn retry_after_is_capped_at_max_delay() {
+        let server = MockServer::scripted(vec![
+            Response::status(429).with_header("Retry-After", "86400"),
+            synced(),
+        ]);
+        let started = std::time::Instant::now();
+        assert!(retrying_api(&server, 1, 50).sync("*").is_ok());
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn retrying_gives_up_after_max_attempts() {
+        let server = MockServer::scripted(Vec::new());
+        let result = retrying_api(&server, 1, 10).sync("*");
+        assert_eq!(result.err().and_then(|error| error.status()), Some(500));
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn retried_quick_adds_keep_their_request_id() {
+        let added = Response::json(serde_json::to_value(task("1")).unwrap());
+        let server = MockServer::scripted(vec![Response::status(503), added]);
+        retrying_api(&server, 1, 10)
+            .quick_add(String::from("buy milk"), "request")
+            .unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests
+            .iter()
+            .all(|request| request.header("x-request-id") == Some("request")));
+    }
+
+    #[test]
+    fn client_errors_are_not_retried() {
+        let server = MockServer::scripted(vec![Response::status(400), synced()]);
+        let result = retrying_api(&server, 1, 10).sync("*");
+        assert_eq!(result.err().and_then(|error| error.status()), Some(400));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn durations_parse_or_explain() {
+        let parsed = |text| TaskDuration::parse(text).map(|x| x.map(|x| x.edit_string()));
+        assert_eq!(parsed("30m"), Ok(Some(String::from("30m"))));
+        assert_eq!(parsed(" 2h "), Ok(Some(String::from("2h"))));
+        assert_eq!(parsed("90m"), Ok(Some(String::from("90m"))));
+        assert_eq!(parsed("1d"), Ok(Some(String::from("1d"))));
+        assert_eq!(parsed(""), Ok(None));
+        assert!(parsed("0m").is_err());
+        assert!(parsed("2w").is_err());
+        assert!(parsed("80000000h").is_err());
+    }
+
+    #[test]
+    fn sorting_keeps_equal_priorities_in_order() {
+        let mut tasks = vec![task("a"), task("b"), task("c")];
+        tasks[2].priority = 1;
+        sort_tasks(&mut tasks);
+        let ids = |tasks: &[Task]| tasks.iter().map(|x| x.get_id()).collect::<Vec<String>>();
+        assert_eq!(ids(&tasks), ["a", "b", "c"]);
+        sort_tasks(&mut tasks);
+        assert_eq!(ids(&tasks), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn delta_sync_drops_completed_and_deleted_tasks() {
+        let mut tasks = vec![task("1"), task("2"), task("3")];
+        let mut checked = task("1");
+        checked.checked = true;
+        let mut deleted = task("2");
+        deleted.is_deleted = true;
+        merge(&mut tasks, vec![checked, deleted, task("4")], false);
+        let ids: Vec<String> = tasks.iter().map(|x| x.get_id()).collect();
+        assert_eq!(ids, ["3", "4"]);
+
+        // a full sync replaces everything, still without inactive tasks
+        let mut deleted = task("5");
+        deleted.is_deleted = true;
+        merge(&mut tasks, vec![task("6"), deleted], true);
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].get_id(), "6");
+    }
 }
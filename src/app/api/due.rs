@@ -0,0 +1,154 @@
+use chrono::{format::StrftimeItems, DateTime, Local, NaiveDate, NaiveDateTime, Utc};
+use serde::Deserialize;
+
+/// A due date parsed from the raw form the API sends
+pub enum DueDate {
+    /// all day, e.g. 2024-05-01
+    Date(NaiveDate),
+    /// at a time in whatever timezone the user is in, e.g. 2024-05-01T09:00:00
+    Floating(NaiveDateTime),
+    /// at a fixed moment, e.g. 2024-05-01T09:00:00Z
+    Fixed(DateTime<Utc>),
+}
+
+impl DueDate {
+    pub fn parse(raw: &str) -> Option<DueDate> {
+        if let Ok(date) = NaiveDate::parse_from_str(raw, "%Y-%m-%d") {
+            return Some(DueDate::Date(date));
+        }
+        if let Ok(datetime) = DateTime::parse_from_rfc3339(raw) {
+            return Some(DueDate::Fixed(datetime.with_timezone(&Utc)));
+        }
+        ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M"]
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(raw, format).ok())
+            .map(DueDate::Floating)
+    }
+
+    pub fn format(&self, format: &DueFormat) -> String {
+        //! Renders the date in the local timezone, e.g. "Today 09:00", "Tomorrow" or "Mon"
+        let (date, time) = match self {
+            DueDate::Date(date) => (*date, None),
+            DueDate::Floating(datetime) => (datetime.date(), Some(datetime.time())),
+            DueDate::Fixed(datetime) => {
+                let local = datetime.with_timezone(&Local).naive_local();
+                (local.date(), Some(local.time()))
+            }
+        };
+        let days = (date - Local::now().date_naive()).num_days();
+        let mut text = match days {
+            _ if !format.relative => date.format(&format.date_format).to_string(),
+            -1 => String::from("Yesterday"),
+            0 => String::from("Today"),
+            1 => String::from("Tomorrow"),
+            2..=6 => date.format("%a").to_string(),
+            _ => date.format(&format.date_format).to_string(),
+        };
+        if let Some(time) = time {
+            text.push(' ');
+            text.push_str(&time.format(&format.time_format).to_string());
+        }
+        text
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+/// How due dates are shown, formats use strftime syntax
+pub struct DueFormat {
+    /// show dates within the next week as Today, Tomorrow or the weekday
+    pub relative: bool,
+    /// for dates not shown relatively
+    pub date_format: String,
+    /// added after the date when a task is due at a time
+    pub time_format: String,
+}
+
+impl Default for DueFormat {
+    fn default() -> DueFormat {
+        DueFormat {
+            relative: true,
+            date_format: String::from("%Y-%m-%d"),
+            time_format: String::from("%H:%M"),
+        }
+    }
+}
+
+impl DueFormat {
+    pub fn validate(&self) -> Result<(), String> {
+        //! Checks the formats are valid, chrono panics when rendering an invalid one
+        for format in [&self.date_format, &self.time_format] {
+            if StrftimeItems::new(format).parse().is_err() {
+                return Err(format!("\"{format}\" is not a valid date format"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Days;
+
+    fn absolute() -> DueFormat {
+        DueFormat {
+            relative: false,
+            ..DueFormat::default()
+        }
+    }
+
+    #[test]
+    fn due_dates_parse_every_form() {
+        let shown = |raw| DueDate::parse(raw).map(|due| due.format(&absolute()));
+        assert_eq!(shown("2024-05-01").as_deref(), Some("2024-05-01"));
+        assert_eq!(
+            shown("2024-05-01T09:00:00").as_deref(),
+            Some("2024-05-01 09:00")
+        );
+        assert_eq!(
+            shown("2024-05-01T09:00").as_deref(),
+            Some("2024-05-01 09:00")
+        );
+        assert_eq!(
+            shown("2024-05-01T09:00:00.000000").as_deref(),
+            Some("2024-05-01 09:00")
+        );
+        // fixed times are moved into the local timezone
+        let local = DateTime::parse_from_rfc3339("2024-05-01T09:00:00Z")
+            .unwrap()
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string();
+        assert_eq!(shown("2024-05-01T09:00:00Z"), Some(local));
+        assert!(shown("next tuesday").is_none());
+        assert!(shown("").is_none());
+    }
+
+    #[test]
+    fn dates_in_the_next_week_are_shown_relatively() {
+        let today = Local::now().date_naive();
+        let shown = |date: NaiveDate| DueDate::Date(date).format(&DueFormat::default());
+        assert_eq!(shown(today - Days::new(1)), "Yesterday");
+        assert_eq!(shown(today), "Today");
+        assert_eq!(shown(today + Days::new(1)), "Tomorrow");
+        let in_three_days = today + Days::new(3);
+        assert_eq!(shown(in_three_days), in_three_days.format("%a").to_string());
+        let next_month = today + Days::new(30);
+        assert_eq!(shown(next_month), next_month.format("%Y-%m-%d").to_string());
+        let last_week = today - Days::new(7);
+        assert_eq!(shown(last_week), last_week.format("%Y-%m-%d").to_string());
+
+        // times are added after, in the configured format
+        let format = DueFormat {
+            time_format: String::from("%I:%M %p"),
+            ..DueFormat::default()
+        };
+        let due = DueDate::Floating(today.and_hms_opt(21, 5, 0).unwrap());
+        assert_eq!(due.format(&format), "Today 09:05 PM");
+        assert_eq!(
+            DueDate::Date(today).format(&absolute()),
+            today.format("%Y-%m-%d").to_string()
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Due, DueDate, DueFormat};
+
+/// Anything synced by id through the sync endpoint
+pub trait Resource {
+    fn id(&self) -> &str;
+    /// False once it has been deleted, archived or completed, so should be dropped locally
+    fn is_active(&self) -> bool;
+}
+
+pub fn merge<T: Resource>(current: &mut Vec<T>, synced: Vec<T>, full_sync: bool) {
+    //! Applies synced resources to a local list, replacing it on a full sync,
+    //! otherwise updating by id and dropping any that are no longer active
+    if full_sync {
+        *current = synced;
+    } else {
+        for resource in synced {
+            match current.iter().position(|x| x.id() == resource.id()) {
+                Some(index) => current[index] = resource,
+                None => current.push(resource),
+            }
+        }
+    }
+    current.retain(|resource| resource.is_active());
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+/// Project representation
+pub struct Project {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub color: String,
+    pub parent_id: Option<String>,
+    #[serde(default, rename = "inbox_project", alias = "is_inbox_project")]
+    pub is_inbox: bool,
+    #[serde(default)]
+    pub child_order: i64,
+    #[serde(default)]
+    pub is_deleted: bool,
+    #[serde(default)]
+    pub is_archived: bool,
+}
+
+impl Resource for Project {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn is_active(&self) -> bool {
+        !(self.is_deleted || self.is_archived)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+/// Section representation, a heading tasks are grouped under inside a project
+pub struct Section {
+    pub id: String,
+    pub name: String,
+    pub project_id: String,
+    #[serde(default)]
+    pub section_order: i64,
+    #[serde(default)]
+    pub is_deleted: bool,
+    #[serde(default)]
+    pub is_archived: bool,
+}
+
+impl Resource for Section {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn is_active(&self) -> bool {
+        !(self.is_deleted || self.is_archived)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+/// Personal label representation
+pub struct Label {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub color: String,
+    #[serde(default)]
+    pub item_order: i64,
+    #[serde(default)]
+    pub is_favorite: bool,
+    #[serde(default)]
+    pub is_deleted: bool,
+}
+
+impl Resource for Label {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn is_active(&self) -> bool {
+        !self.is_deleted
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+/// Comment on a task
+pub struct Note {
+    pub id: String,
+    /// id of the task it is on
+    pub item_id: String,
+    pub content: String,
+    #[serde(default)]
+    pub posted_at: String,
+    /// id of the user that wrote it
+    #[serde(default)]
+    pub posted_uid: String,
+    #[serde(default)]
+    pub is_deleted: bool,
+}
+
+impl Note {
+    pub fn posted_time(&self, format: &DueFormat) -> String {
+        //! When it was posted in local time, shown like due dates
+        match DueDate::parse(&self.posted_at) {
+            Some(time) => time.format(format),
+            None => self.posted_at.clone(),
+        }
+    }
+}
+
+impl Resource for Note {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn is_active(&self) -> bool {
+        !self.is_deleted
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+/// The user the API token belongs to
+pub struct User {
+    pub id: String,
+    #[serde(default)]
+    pub full_name: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+/// Someone sharing a project with the user
+pub struct Collaborator {
+    pub id: String,
+    #[serde(default)]
+    pub full_name: String,
+}
+
+impl Resource for Collaborator {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn is_active(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+/// Reminder for a task
+pub struct Reminder {
+    pub id: String,
+    /// id of the task it is for
+    pub item_id: String,
+    /// absolute, relative or location
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    /// when an absolute reminder goes off
+    #[serde(default)]
+    pub due: Option<Due>,
+    /// how long before the task is due a relative reminder goes off
+    #[serde(default)]
+    pub minute_offset: Option<i64>,
+    #[serde(default)]
+    pub is_deleted: bool,
+}
+
+impl Reminder {
+    pub fn describe(&self, format: &DueFormat) -> String {
+        //! When it goes off, e.g. "Tomorrow 09:00" or "30 minutes before due"
+        match (self.kind.as_str(), &self.due, self.minute_offset) {
+            ("absolute", Some(due), _) => match DueDate::parse(&due.date) {
+                Some(time) => time.format(format),
+                None => due.date.clone(),
+            },
+            ("relative", _, Some(minutes)) if minutes % 1440 == 0 => {
+                format!("{} days before due", minutes / 1440)
+            }
+            ("relative", _, Some(minutes)) if minutes % 60 == 0 => {
+                format!("{} hours before due", minutes / 60)
+            }
+            ("relative", _, Some(minutes)) => format!("{minutes} minutes before due"),
+            ("location", _, _) => String::from("at a location"),
+            (kind, _, _) => format!("{kind} reminder"),
+        }
+    }
+}
+
+impl Resource for Reminder {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn is_active(&self) -> bool {
+        !self.is_deleted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Local};
+
+    #[test]
+    fn times_are_shown_in_local_time_like_due_dates() {
+        let format = DueFormat {
+            relative: false,
+            ..DueFormat::default()
+        };
+        let local = DateTime::parse_from_rfc3339("2024-05-01T09:30:00Z")
+            .unwrap()
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string();
+        let note: Note = serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "item_id": "1",
+            "content": "",
+            "posted_at": "2024-05-01T09:30:00.000000Z",
+        }))
+        .unwrap();
+        assert_eq!(note.posted_time(&format), local);
+
+        let reminder: Reminder = serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "item_id": "1",
+            "type": "absolute",
+            "due": {"date": "2024-05-01T09:30:00Z"},
+        }))
+        .unwrap();
+        assert_eq!(reminder.describe(&format), local);
+    }
+}
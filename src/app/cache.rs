@@ -0,0 +1,124 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::{env, fs, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    api::{SyncCommand, SyncData},
+    App, Notice,
+};
+
+#[derive(Serialize, Deserialize)]
+/// Contents of the cache file, the synced state as if it came from a full sync
+struct Cache {
+    /// cached state is only used with the API and account it was synced from
+    api_url: String,
+    #[serde(default)]
+    account: String,
+    state: SyncData,
+    /// changes already applied to the state that the server hasn't seen yet
+    #[serde(default)]
+    outbox: Vec<SyncCommand>,
+}
+
+pub(super) fn cache_path() -> Option<PathBuf> {
+    //! $XDG_DATA_HOME/todoist-rs/cache.json, or ~/.local/share/todoist-rs/cache.json
+    let data_home = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?)
+            .join(".local")
+            .join("share"),
+    };
+    Some(data_home.join("todoist-rs").join("cache.json"))
+}
+
+pub(super) fn account_key(token: &str) -> String {
+    //! Tells accounts apart without saving their token. Only needs to stay the
+    //! same between runs of one build, a change just means a full sync
+    let mut hasher = DefaultHasher::new();
+    token.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+impl App {
+    pub(super) fn load_cache(&mut self) {
+        //! Shows the state saved by the last run, if there is one for this API and
+        //! account, so the next sync only fetches what has changed since
+        let Some(path) = self.cache_path.clone().filter(|path| path.exists()) else {
+            return;
+        };
+        let cache = fs::read_to_string(&path)
+            .map_err(|error| error.to_string())
+            .and_then(|contents| {
+                serde_json::from_str::<Cache>(&contents).map_err(|error| error.to_string())
+            });
+        match cache {
+            Ok(cache) if cache.api_url == self.api_url && cache.account == self.account => {
+                self.apply_sync(cache.state);
+                self.outbox = cache.outbox;
+            }
+            Ok(_) => {}
+            Err(error) => {
+                self.notice = Some(Notice {
+                    message: format!("ignored saved tasks in {}: {error}", path.display()),
+                    retry: None,
+                })
+            }
+        }
+    }
+
+    pub(super) fn save_cache(&self) -> Result<(), String> {
+        //! Writes the synced state along with its sync token, replacing the file in one go
+        //! so a crash part way through never leaves a broken cache behind.
+        //! Only the user can read it, as it holds every task and comment
+        let Some(path) = self.cache_path.clone() else {
+            return Err(String::from(
+                "could not find a home directory to save tasks in",
+            ));
+        };
+        let cache = Cache {
+            api_url: self.api_url.clone(),
+            account: self.account.clone(),
+            state: SyncData {
+                full_sync: true,
+                sync_token: self.current_sync_token.clone(),
+                items: self.tasks.clone(),
+                projects: self.projects.clone(),
+                sections: self.sections.clone(),
+                labels: self.labels.clone(),
+                notes: self.notes.clone(),
+                reminders: self.reminders.clone(),
+                user: self.user.clone(),
+                collaborators: self.collaborators.clone(),
+            },
+            outbox: self.outbox.clone(),
+        };
+        let contents = serde_json::to_string(&cache).expect("synced state always serializes");
+        let temporary = path.with_extension("json.tmp");
+        let write = || -> std::io::Result<()> {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let _ = fs::remove_file(&temporary);
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            options.open(&temporary)?.write_all(contents.as_bytes())?;
+            fs::rename(&temporary, &path)
+        };
+        write().map_err(|error| format!("could not save tasks to {}: {error}", path.display()))
+    }
+
+    pub(super) fn persist(&mut self) {
+        //! Saves the cache, reporting in the status bar if it couldn't be
+        if let Err(message) = self.save_cache() {
+            self.notice = Some(Notice {
+                message,
+                retry: None,
+            });
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use crossterm::event::KeyCode;
+
+use super::{
+    api::{Command, Resource, Section, SyncCommand},
+    labels::fuzzy_score,
+    tree::{move_command, Confirm},
+    undo::Undo,
+    App, Mode,
+};
+
+/// What happens to the task once somewhere is chosen
+pub enum Action {
+    Move,
+    Duplicate,
+}
+
+#[derive(Clone)]
+/// Somewhere a task can be moved or duplicated to
+pub enum Destination {
+    /// top level of a project, without a section
+    Project(String),
+    Section {
+        project_id: String,
+        id: String,
+    },
+    /// sub-task of another task, in its project and section
+    Parent(String),
+}
+
+/// List of projects, sections and tasks to put a task in, narrowed down by typing
+pub struct Chooser {
+    pub action: Action,
+    pub task_id: String,
+    pub query: String,
+    /// index into the rows matching the query
+    pub position: usize,
+}
+
+impl Chooser {
+    pub fn title(&self) -> String {
+        match self.action {
+            Action::Move => format!(" move to: {} ", self.query),
+            Action::Duplicate => format!(" duplicate to: {} ", self.query),
+        }
+    }
+}
+
+impl App {
+    pub(super) fn chooser_rows(&self, chooser: &Chooser) -> Vec<(String, Destination)> {
+        //! Every project in sidebar order followed by its sections and tasks, best
+        //! match for the query first. A task can't be put under itself or its sub-tasks
+        let mut excluded = self.descendants(&chooser.task_id);
+        excluded.push(chooser.task_id.clone());
+        let mut rows = Vec::new();
+        for index in self.project_order() {
+            let project = &self.projects[index];
+            rows.push((
+                project.name.clone(),
+                Destination::Project(project.id.clone()),
+            ));
+            let mut sections: Vec<&Section> = self
+                .sections
+                .iter()
+                .filter(|section| section.project_id == project.id)
+                .collect();
+            sections.sort_by_key(|section| section.section_order);
+            for section in sections {
+                rows.push((
+                    format!("{} / {}", project.name, section.name),
+                    Destination::Section {
+                        project_id: project.id.clone(),
+                        id: section.id.clone(),
+                    },
+                ));
+            }
+            for task in self.tasks.iter().filter(|task| {
+                task.project_id() == project.id && !excluded.contains(&task.get_id())
+            }) {
+                rows.push((
+                    format!("{} › {}", project.name, task.content()),
+                    Destination::Parent(task.get_id()),
+                ));
+            }
+        }
+
+        let mut rows: Vec<(i64, (String, Destination))> = rows
+            .into_iter()
+            .filter_map(|(text, destination)| {
+                Some((fuzzy_score(&chooser.query, &text)?, (text, destination)))
+            })
+            .collect();
+        rows.sort_by_key(|(score, _)| -score);
+        rows.into_iter().map(|(_, row)| row).collect()
+    }
+
+    pub(super) fn open_chooser(&mut self, action: Action) {
+        let Some(index) = self.selected_task() else {
+            return;
+        };
+        self.mode = Mode::Choose(Chooser {
+            action,
+            task_id: self.tasks[index].get_id(),
+            query: String::new(),
+            position: 0,
+        });
+    }
+
+    pub(super) fn handle_chooser_key(&mut self, key: KeyCode) {
+        //! Key handling while choosing where a task goes, typing narrows down the list
+        let Mode::Choose(chooser) = &self.mode else {
+            return;
+        };
+        let rows = self.chooser_rows(chooser);
+        let Mode::Choose(chooser) = &mut self.mode else {
+            return;
+        };
+        match key {
+            KeyCode::Down if chooser.position + 1 < rows.len() => chooser.position += 1,
+            KeyCode::Up => chooser.position = chooser.position.saturating_sub(1),
+            KeyCode::Char(character) => {
+                chooser.query.push(character);
+                chooser.position = 0;
+            }
+            KeyCode::Backspace => {
+                chooser.query.pop();
+                chooser.position = 0;
+            }
+            KeyCode::Enter => {
+                let Mode::Choose(chooser) = std::mem::replace(&mut self.mode, Mode::Normal) else {
+                    return;
+                };
+                if let Some((_, destination)) = rows.into_iter().nth(chooser.position) {
+                    match chooser.action {
+                        Action::Move => self.move_task(&chooser.task_id, destination),
+                        Action::Duplicate => self.duplicate_task(&chooser.task_id, destination),
+                    }
+                }
+            }
+            KeyCode::Esc => self.mode = Mode::Normal,
+            _ => {}
+        }
+    }
+
+    fn place(&self, destination: &Destination) -> Option<(String, Option<String>, Option<String>)> {
+        //! (project id, section id, parent id) of a destination
+        match destination {
+            Destination::Project(id) => Some((id.clone(), None, None)),
+            Destination::Section { project_id, id } => {
+                Some((project_id.clone(), Some(id.clone()), None))
+            }
+            Destination::Parent(id) => {
+                let parent = self.tasks.iter().find(|task| task.id() == id)?;
+                Some((
+                    parent.project_id().to_string(),
+                    parent.section_id().map(String::from),
+                    Some(id.clone()),
+                ))
+            }
+        }
+    }
+
+    fn move_task(&mut self, id: &str, destination: Destination) {
+        //! Moves the task locally, sub-tasks going with it, and queues the item_move
+        let Some((project_id, section_id, parent_id)) = self.place(&destination) else {
+            return;
+        };
+        let mut moved = self.descendants(id);
+        moved.push(id.to_string());
+        let snapshots: Vec<_> = self
+            .tasks
+            .iter()
+            .filter(|task| moved.contains(&task.get_id()))
+            .cloned()
+            .collect();
+        let Some(old) = snapshots.iter().find(|task| task.id() == id) else {
+            return;
+        };
+        let command = match destination {
+            Destination::Project(project_id) => Command::ItemMove {
+                id: id.to_string(),
+                parent_id: None,
+                section_id: None,
+                project_id: Some(project_id),
+            },
+            Destination::Section { id: section_id, .. } => Command::ItemMove {
+                id: id.to_string(),
+                parent_id: None,
+                section_id: Some(section_id),
+                project_id: None,
+            },
+            Destination::Parent(parent_id) => move_command(old, Some(&parent_id)),
+        };
+        let undo = Undo {
+            action: "task moved",
+            commands: vec![SyncCommand::new(move_command(old, old.parent_id()))],
+            tasks: snapshots,
+        };
+
+        self.update_tasks(|tasks| {
+            for task in tasks
+                .iter_mut()
+                .filter(|task| moved.contains(&task.get_id()))
+            {
+                task.set_place(project_id.clone(), section_id.clone());
+                if task.id() == id {
+                    task.set_parent_id(parent_id.clone());
+                }
+            }
+        });
+        self.queue(vec![SyncCommand::new(command)]);
+        self.record_undo(undo);
+    }
+
+    fn duplicate_task(&mut self, id: &str, destination: Destination) {
+        //! Adds a copy of the task there, with its description, labels, priority and dates
+        let Some((project_id, section_id, parent_id)) = self.place(&destination) else {
+            return;
+        };
+        let Some(task) = self.tasks.iter().find(|task| task.id() == id) else {
+            return;
+        };
+        let temp_id = uuid::Uuid::new_v4().to_string();
+        let mut copy = task.duplicate(temp_id.clone());
+        copy.set_place(project_id, section_id);
+        copy.set_parent_id(parent_id);
+        let command = SyncCommand::new(copy.add_command()).with_temp_id(temp_id);
+        self.update_tasks(|tasks| tasks.push(copy));
+        self.queue(vec![command]);
+        self.toast(String::from("task duplicated"));
+    }
+
+    pub(super) fn confirm_delete(&mut self) {
+        let Some(index) = self.selected_task() else {
+            return;
+        };
+        let task = self.tasks[index].clone();
+        self.mode = Mode::Confirm(Box::new(Confirm::DeleteTask(task)));
+    }
+
+    pub(super) fn delete_task(&mut self, id: &str) {
+        //! Deletes the task and its sub-tasks locally and queues the item_delete.
+        //! Undoing adds them again as new tasks
+        let mut deleted = vec![id.to_string()];
+        deleted.extend(self.descendants(id));
+        // parents first, so undoing can add sub-tasks under them
+        let snapshots = deleted
+            .iter()
+            .filter_map(|id| self.tasks.iter().find(|task| task.id() == id).cloned())
+            .collect();
+        self.update_tasks(|tasks| tasks.retain(|task| !deleted.contains(&task.get_id())));
+        self.queue(vec![SyncCommand::new(Command::ItemDelete {
+            id: id.to_string(),
+        })]);
+        self.record_undo(Undo::deleted(snapshots));
+    }
+}
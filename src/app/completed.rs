@@ -0,0 +1,139 @@
+use chrono::{Days, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use crossterm::event::KeyCode;
+
+use super::{
+    api::{ApiError, Command, CompletedTask, DueDate, Resource, SyncCommand},
+    worker, App, Error, Mode,
+};
+
+/// Length of the date range shown at once
+const PAGE_DAYS: u64 = 7;
+
+fn page_dates(page: u64) -> (NaiveDate, NaiveDate) {
+    //! First and last day of a page of the completed view, 0 being the week up to today
+    let last = Local::now().date_naive() - Days::new(page * PAGE_DAYS);
+    (last - Days::new(PAGE_DAYS - 1), last)
+}
+
+fn utc_midnight(date: NaiveDate) -> String {
+    //! Start of the local day as the API expects it, e.g. 2024-05-01T23:00:00Z
+    let midnight = date.and_time(NaiveTime::MIN);
+    let utc = match Local.from_local_datetime(&midnight).earliest() {
+        Some(local) => local.with_timezone(&Utc),
+        // midnight skipped by a daylight saving change
+        None => midnight.and_utc(),
+    };
+    utc.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+fn page_range(page: u64) -> (String, String) {
+    //! (since, until) UTC times covering every day of the page
+    let (first, last) = page_dates(page);
+    (utc_midnight(first), utc_midnight(last + Days::new(1)))
+}
+
+impl App {
+    pub(super) fn open_completed(&mut self) {
+        self.mode = Mode::Completed;
+        self.completed_page = 0;
+        self.fetch_completed();
+    }
+
+    fn fetch_completed(&mut self) {
+        self.completed.clear();
+        let (since, until) = page_range(self.completed_page);
+        self.worker
+            .send(worker::Request::FetchCompleted(since, until));
+    }
+
+    pub(super) fn fetched_completed(
+        &mut self,
+        since: String,
+        until: String,
+        result: Result<Vec<CompletedTask>, ApiError>,
+    ) -> Result<(), Error> {
+        //! Shows the completed tasks, unless the view has moved on to another page since
+        let retry = worker::Request::FetchCompleted(since.clone(), until.clone());
+        let Some(mut completed) = self.check_result(result, retry)? else {
+            return Ok(());
+        };
+        if (since, until) != page_range(self.completed_page) {
+            return Ok(());
+        }
+        completed.sort_by(|a, b| b.completed_at.cmp(&a.completed_at));
+        self.completed = completed;
+        self.completed_position
+            .select((!self.completed.is_empty()).then_some(0));
+        Ok(())
+    }
+
+    pub(super) fn completed_title(&self) -> String {
+        let (first, last) = page_dates(self.completed_page);
+        format!(" completed {first} to {last} ")
+    }
+
+    pub(super) fn completed_rows_text(&self) -> Vec<String> {
+        //! Completed tasks as when they were completed, the task and its project
+        self.completed
+            .iter()
+            .map(|completed| {
+                let time = match DueDate::parse(&completed.completed_at) {
+                    Some(time) => time.format(&self.due_format),
+                    None => completed.completed_at.clone(),
+                };
+                format!(
+                    "{time:16} {} · {}",
+                    completed.task.content(),
+                    self.project_name(completed.task.project_id())
+                )
+            })
+            .collect()
+    }
+
+    pub(super) fn handle_completed_key(&mut self, key: KeyCode) {
+        //! Paging through completed tasks a week at a time and restoring them
+        let selected = self.completed_position.selected().unwrap_or(0);
+        match key {
+            KeyCode::Char('j') | KeyCode::Down if selected + 1 < self.completed.len() => {
+                self.completed_position.select(Some(selected + 1))
+            }
+            KeyCode::Char('k') | KeyCode::Up => self
+                .completed_position
+                .select(Some(selected.saturating_sub(1))),
+            // older and newer weeks
+            KeyCode::Char('h') | KeyCode::Left => {
+                self.completed_page += 1;
+                self.fetch_completed();
+            }
+            KeyCode::Char('l') | KeyCode::Right if self.completed_page > 0 => {
+                self.completed_page -= 1;
+                self.fetch_completed();
+            }
+            KeyCode::Char('U') => self.fetch_completed(),
+            KeyCode::Enter => self.restore_completed(selected),
+            KeyCode::Esc | KeyCode::Backspace | KeyCode::Char('q') => self.mode = Mode::Normal,
+            _ => {}
+        }
+    }
+
+    fn restore_completed(&mut self, index: usize) {
+        //! Uncompletes the task, putting it back in the task list straight away
+        if index >= self.completed.len() {
+            return;
+        }
+        let task = self.completed.remove(index).task.uncompleted();
+        if index >= self.completed.len() {
+            self.completed_position
+                .select(self.completed.len().checked_sub(1));
+        }
+        let command = Command::ItemUncomplete { id: task.get_id() };
+        self.update_tasks(
+            |tasks| match tasks.iter_mut().find(|x| x.id() == task.id()) {
+                Some(current) => *current = task,
+                None => tasks.push(task),
+            },
+        );
+        self.queue(vec![SyncCommand::new(command)]);
+        self.toast(String::from("task restored"));
+    }
+}
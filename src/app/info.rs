@@ -0,0 +1,149 @@
+use crossterm::event::KeyCode;
+
+use super::{
+    api::{Command, Note, Reminder, Resource},
+    manage::Form,
+    App,
+};
+
+impl App {
+    fn task_notes(&self, task_id: &str) -> Vec<&Note> {
+        //! Comments on the task, oldest first
+        let mut notes: Vec<&Note> = self
+            .notes
+            .iter()
+            .filter(|note| note.item_id == task_id)
+            .collect();
+        notes.sort_by(|a, b| a.posted_at.cmp(&b.posted_at));
+        notes
+    }
+
+    fn task_reminders(&self, task_id: &str) -> Vec<&Reminder> {
+        self.reminders
+            .iter()
+            .filter(|reminder| reminder.item_id == task_id)
+            .collect()
+    }
+
+    fn info_selected(&self) -> Option<&str> {
+        //! Id of the reminder or comment picked with tab, if it is on the selected task
+        let task = &self.tasks[self.selected_task()?];
+        let id = self.info_selected.as_deref()?;
+        let on_task = self.task_reminders(task.id()).iter().any(|x| x.id() == id)
+            || self.task_notes(task.id()).iter().any(|x| x.id() == id);
+        on_task.then_some(id)
+    }
+
+    fn author_name(&self, user_id: &str) -> &str {
+        if let Some(user) = self.user.as_ref().filter(|user| user.id == user_id) {
+            return &user.full_name;
+        }
+        self.collaborators
+            .iter()
+            .find(|collaborator| collaborator.id() == user_id)
+            .map(|collaborator| collaborator.full_name.as_str())
+            .unwrap_or("unknown")
+    }
+
+    pub(super) fn info_text(&self, index: usize) -> String {
+        //! Task details for the info pane, followed by its reminders and comments
+        let task = &self.tasks[index];
+        let mut text = task.to_info_string(&self.due_format);
+        let selected = self.info_selected();
+        let marker = |id: &str| match selected == Some(id) {
+            true => "> ",
+            false => "",
+        };
+
+        let reminders = self.task_reminders(task.id());
+        if !reminders.is_empty() {
+            text.push_str("\n\n--- reminders (tab to select) ---");
+        }
+        for reminder in reminders {
+            text.push_str(&format!(
+                "\n{}{}",
+                marker(&reminder.id),
+                reminder.describe(&self.due_format)
+            ));
+        }
+
+        let notes = self.task_notes(task.id());
+        if !notes.is_empty() {
+            text.push_str("\n\n--- comments (tab to select) ---");
+        }
+        for note in notes {
+            text.push_str(&format!(
+                "\n\n{}{} · {}\n{}",
+                marker(&note.id),
+                self.author_name(&note.posted_uid),
+                note.posted_time(&self.due_format),
+                note.content,
+            ));
+        }
+        text
+    }
+
+    pub(super) fn handle_info_key(&mut self, key: KeyCode) -> bool {
+        //! Reminder and comment actions in the info pane, returns false if the key isn't one of them
+        let Some(index) = self.selected_task() else {
+            return false;
+        };
+        let task_id = self.tasks[index].get_id();
+        let selected = self.info_selected().map(String::from);
+        let note = selected
+            .as_ref()
+            .and_then(|id| self.notes.iter().find(|note| note.id() == id));
+        match (key, note) {
+            // cycles through the reminders then comments, then back to none
+            (KeyCode::Tab, _) => {
+                let ids: Vec<&str> = self
+                    .task_reminders(&task_id)
+                    .iter()
+                    .map(|x| x.id())
+                    .chain(self.task_notes(&task_id).iter().map(|x| x.id()))
+                    .collect();
+                let next = match &selected {
+                    None => ids.first(),
+                    Some(selected) => ids.iter().skip_while(|id| *id != selected).nth(1),
+                };
+                self.info_selected = next.map(|id| id.to_string());
+            }
+            (KeyCode::Char('a'), _) => self.open_form(
+                Form::Note {
+                    item_id: task_id,
+                    id: None,
+                },
+                Vec::new(),
+            ),
+            (KeyCode::Char('R'), _) => {
+                self.open_form(Form::Reminder { item_id: task_id }, Vec::new())
+            }
+            (KeyCode::Char('e'), Some(note)) => {
+                let values = vec![note.content.clone()];
+                let form = Form::Note {
+                    item_id: task_id,
+                    id: Some(note.id.clone()),
+                };
+                self.open_form(form, values)
+            }
+            (KeyCode::Char('D'), Some(note)) => {
+                let id = note.id.clone();
+                self.confirm_command(
+                    String::from("delete the selected comment?"),
+                    Command::NoteDelete { id },
+                )
+            }
+            (KeyCode::Char('D'), None) => {
+                let Some(id) = selected else {
+                    return false;
+                };
+                self.confirm_command(
+                    String::from("delete the selected reminder?"),
+                    Command::ReminderDelete { id },
+                )
+            }
+            _ => return false,
+        }
+        true
+    }
+}
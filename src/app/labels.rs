@@ -0,0 +1,175 @@
+use crossterm::event::KeyCode;
+
+use super::{api::Label, App, Mode};
+
+/// What the chosen labels are used for
+pub enum Purpose {
+    /// the labels input of edit mode
+    Edit,
+    /// only list tasks that have every chosen label
+    Filter,
+}
+
+/// Multi-select list of labels, narrowed down by typing
+pub struct Picker {
+    pub purpose: Purpose,
+    pub query: String,
+    pub chosen: Vec<String>,
+    /// index into the rows matching the query
+    pub position: usize,
+}
+
+pub fn split_labels(text: &str) -> Vec<String> {
+    //! Label names from a comma separated list, ignoring spaces around them
+    text.split(',')
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+pub fn fuzzy_score(query: &str, name: &str) -> Option<i64> {
+    //! Some score if the query's characters appear in order in the name, ignoring case.
+    //! Runs of consecutive characters and matches near the start score higher
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut from = 0;
+    for character in query.to_lowercase().chars() {
+        let found = (from..name.len()).find(|&index| name[index] == character)?;
+        score += match found == from {
+            true => 3,
+            false => -((found - from) as i64).min(3),
+        };
+        from = found + 1;
+    }
+    Some(score)
+}
+
+impl App {
+    pub(super) fn label_order(&self) -> Vec<&Label> {
+        //! Synced labels, favourites first then in the user's order
+        let mut labels: Vec<&Label> = self.labels.iter().collect();
+        labels.sort_by_key(|label| (!label.is_favorite, label.item_order));
+        labels
+    }
+
+    pub(super) fn picker_rows(&self, picker: &Picker) -> Vec<(String, String, bool)> {
+        //! Labels matching the query as (name, todoist colour, chosen), best match first.
+        //! Shared labels and any already chosen are included even though they aren't synced
+        let mut names: Vec<(String, String)> = self
+            .label_order()
+            .iter()
+            .map(|label| (label.name.clone(), label.color.clone()))
+            .collect();
+        let unsynced = self.tasks.iter().flat_map(|task| task.labels());
+        for name in picker.chosen.iter().chain(unsynced) {
+            if !names.iter().any(|(x, _)| x == name) {
+                names.push((name.clone(), String::new()));
+            }
+        }
+
+        let mut rows: Vec<(i64, (String, String, bool))> = names
+            .into_iter()
+            .filter_map(|(name, color)| {
+                let score = fuzzy_score(&picker.query, &name)?;
+                let chosen = picker.chosen.contains(&name);
+                Some((score, (name, color, chosen)))
+            })
+            .collect();
+        rows.sort_by_key(|(score, _)| -score);
+        rows.into_iter().map(|(_, row)| row).collect()
+    }
+
+    pub(super) fn open_picker(&mut self, purpose: Purpose) {
+        let chosen = match purpose {
+            Purpose::Edit => split_labels(&self.inputs[2]),
+            Purpose::Filter => self.label_filter.clone(),
+        };
+        self.mode = Mode::PickLabels(Picker {
+            purpose,
+            query: String::new(),
+            chosen,
+            position: 0,
+        });
+    }
+
+    pub(super) fn handle_picker_key(&mut self, key: KeyCode) {
+        //! Key handling while picking labels, typing narrows down the list
+        let Mode::PickLabels(picker) = &self.mode else {
+            return;
+        };
+        let rows = self.picker_rows(picker);
+        let Mode::PickLabels(picker) = &mut self.mode else {
+            return;
+        };
+        match key {
+            KeyCode::Down if picker.position + 1 < rows.len() => picker.position += 1,
+            KeyCode::Up => picker.position = picker.position.saturating_sub(1),
+            KeyCode::Tab => {
+                if let Some((name, _, chosen)) = rows.get(picker.position) {
+                    match chosen {
+                        true => picker.chosen.retain(|x| x != name),
+                        false => picker.chosen.push(name.clone()),
+                    }
+                }
+            }
+            KeyCode::Char(character) => {
+                picker.query.push(character);
+                picker.position = 0;
+            }
+            KeyCode::Backspace => {
+                picker.query.pop();
+                picker.position = 0;
+            }
+            KeyCode::Enter => {
+                let Mode::PickLabels(picker) = std::mem::replace(&mut self.mode, Mode::Normal)
+                else {
+                    return;
+                };
+                match picker.purpose {
+                    Purpose::Edit => {
+                        self.inputs[2] = picker.chosen.join(", ");
+                        self.mode = Mode::Edit;
+                    }
+                    Purpose::Filter => {
+                        self.label_filter = picker.chosen;
+                        let selected_id =
+                            self.selected_task().map(|index| self.tasks[index].get_id());
+                        self.refresh_view(selected_id);
+                    }
+                }
+            }
+            KeyCode::Esc => {
+                self.mode = match picker.purpose {
+                    Purpose::Edit => Mode::Edit,
+                    Purpose::Filter => Mode::Normal,
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joined_labels_split_back_trimmed() {
+        let labels = vec![String::from("shop"), String::from("errand")];
+        assert_eq!(split_labels(&labels.join(", ")), labels);
+        assert_eq!(split_labels(" shop ,errand,, "), labels);
+        assert!(split_labels("").is_empty());
+    }
+
+    #[test]
+    fn fuzzy_ranking_prefers_consecutive_and_prefix_matches() {
+        assert_eq!(fuzzy_score("xyz", "shop"), None);
+        assert_eq!(fuzzy_score("ps", "shop"), None);
+        assert!(fuzzy_score("SH", "shop").is_some());
+        // a prefix beats the same letters further in
+        assert!(fuzzy_score("sh", "shop") > fuzzy_score("sh", "washing"));
+        // consecutive letters beat spread out ones
+        assert!(fuzzy_score("wor", "work") > fuzzy_score("wor", "waiting for"));
+    }
+}
@@ -0,0 +1,437 @@
+use chrono::NaiveDateTime;
+use crossterm::event::KeyCode;
+
+use super::{
+    api::{Command, CommandBatch, DueArgs, ReminderKind, Resource},
+    tree::Confirm,
+    ui, worker, App, Mode, Notice,
+};
+
+#[derive(Clone)]
+/// Lists of resources that can be added to, renamed, archived and deleted
+pub enum Manage {
+    /// sections of one project
+    Sections {
+        project_id: String,
+    },
+    Labels,
+}
+
+/// What a form adds or updates, a None id adds a new one
+pub enum Form {
+    Project {
+        id: Option<String>,
+    },
+    Section {
+        project_id: String,
+        id: Option<String>,
+    },
+    Label {
+        id: Option<String>,
+    },
+    /// comment on a task
+    Note {
+        item_id: String,
+        id: Option<String>,
+    },
+    /// reminders can only be added, not changed
+    Reminder {
+        item_id: String,
+    },
+}
+
+impl Form {
+    pub fn title(&self) -> &'static str {
+        match self {
+            Form::Project { id: None } => " new project ",
+            Form::Project { id: Some(_) } => " edit project ",
+            Form::Section { id: None, .. } => " new section ",
+            Form::Section { id: Some(_), .. } => " rename section ",
+            Form::Label { id: None } => " new label ",
+            Form::Label { id: Some(_) } => " edit label ",
+            Form::Note { id: None, .. } => " new comment ",
+            Form::Note { id: Some(_), .. } => " edit comment ",
+            Form::Reminder { .. } => " new reminder ",
+        }
+    }
+
+    pub fn fields(&self) -> &'static [&'static str] {
+        //! Titles of the inputs the form is filled in with, in order
+        match self {
+            Form::Project { .. } | Form::Label { .. } => &[" name ", " colour (e.g. berry_red) "],
+            Form::Section { .. } => &[" name "],
+            Form::Note { .. } => &[" comment "],
+            Form::Reminder { .. } => &[" when (2024-05-01 09:00 or 30m/2h/1d before) "],
+        }
+    }
+
+    fn back(&self) -> Mode {
+        //! Mode the form was opened from
+        match self {
+            Form::Project { .. } => Mode::Projects,
+            Form::Section { project_id, .. } => Mode::Manage(Manage::Sections {
+                project_id: project_id.clone(),
+            }),
+            Form::Label { .. } => Mode::Manage(Manage::Labels),
+            Form::Note { .. } | Form::Reminder { .. } => Mode::Info,
+        }
+    }
+
+    fn command(&self, name: String, color: Option<String>) -> Result<Command, String> {
+        //! Command sending the form, name is the first input whatever it is titled.
+        //! Returns why the input is invalid if it can't be sent
+        let command = match self {
+            Form::Project { id: None } => Command::ProjectAdd { name, color },
+            Form::Project { id: Some(id) } => Command::ProjectUpdate {
+                id: id.clone(),
+                name,
+                color,
+            },
+            Form::Section {
+                project_id,
+                id: None,
+            } => Command::SectionAdd {
+                name,
+                project_id: project_id.clone(),
+            },
+            Form::Section { id: Some(id), .. } => Command::SectionUpdate {
+                id: id.clone(),
+                name,
+            },
+            Form::Label { id: None } => Command::LabelAdd { name, color },
+            Form::Label { id: Some(id) } => Command::LabelUpdate {
+                id: id.clone(),
+                name,
+                color,
+            },
+            Form::Note { item_id, id: None } => Command::NoteAdd {
+                item_id: item_id.clone(),
+                content: name,
+            },
+            Form::Note { id: Some(id), .. } => Command::NoteUpdate {
+                id: id.clone(),
+                content: name,
+            },
+            Form::Reminder { item_id } => reminder_command(item_id, &name)?,
+        };
+        Ok(command)
+    }
+}
+
+fn reminder_command(item_id: &str, when: &str) -> Result<Command, String> {
+    //! reminder_add from a date and time, or from a number of minutes, hours or days before due
+    let item_id = item_id.to_string();
+    let unit = match when.chars().last() {
+        Some('m') => Some(1),
+        Some('h') => Some(60),
+        Some('d') => Some(1440),
+        _ => None,
+    };
+    if let Some(unit) = unit {
+        let count = when[..when.len() - 1].trim().parse::<i64>().ok();
+        return match count
+            .filter(|count| *count >= 0)
+            .and_then(|count| count.checked_mul(unit))
+        {
+            Some(minutes) => Ok(Command::ReminderAdd {
+                item_id,
+                kind: ReminderKind::Relative,
+                due: None,
+                minute_offset: Some(minutes),
+            }),
+            None => Err(format!(
+                "\"{when}\" is not a number of minutes, hours or days"
+            )),
+        };
+    }
+
+    // YYYY-MM-DD HH:MM, sent with the seconds and T separator the API wants
+    let time = ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(when, format).ok());
+    let Some(time) = time else {
+        return Err(format!(
+            "\"{when}\" is not a date and time like 2024-05-01 09:00"
+        ));
+    };
+    Ok(Command::ReminderAdd {
+        item_id,
+        kind: ReminderKind::Absolute,
+        due: Some(DueArgs::date(time.format("%Y-%m-%dT%H:%M:%S").to_string())),
+        minute_offset: None,
+    })
+}
+
+impl App {
+    pub(super) fn manage_rows(&self, manage: &Manage) -> Vec<(String, String, String)> {
+        //! Listed resources as (id, name, todoist colour name)
+        match manage {
+            Manage::Sections { project_id } => {
+                let mut sections: Vec<_> = self
+                    .sections
+                    .iter()
+                    .filter(|section| &section.project_id == project_id)
+                    .collect();
+                sections.sort_by_key(|section| section.section_order);
+                sections
+                    .iter()
+                    .map(|x| (x.id.clone(), x.name.clone(), String::new()))
+                    .collect()
+            }
+            Manage::Labels => self
+                .label_order()
+                .iter()
+                .map(|x| (x.id.clone(), x.name.clone(), x.color.clone()))
+                .collect(),
+        }
+    }
+
+    pub(super) fn manage_title(&self, manage: &Manage) -> String {
+        match manage {
+            Manage::Sections { project_id } => {
+                format!(" sections of {} ", self.project_name(project_id))
+            }
+            Manage::Labels => String::from(" labels "),
+        }
+    }
+
+    pub(super) fn open_manage(&mut self, manage: Manage) {
+        self.manage_position.select(Some(0));
+        self.mode = Mode::Manage(manage);
+    }
+
+    pub(super) fn open_sections(&mut self) {
+        //! Manages the sections of the project being listed, or of the selected task's project
+        let project_id = self.project_filter.clone().or_else(|| {
+            self.selected_task()
+                .map(|index| self.tasks[index].project_id().to_string())
+        });
+        match project_id {
+            Some(project_id) => self.open_manage(Manage::Sections { project_id }),
+            None => {
+                self.notice = Some(Notice {
+                    message: String::from("select a task or project to manage its sections"),
+                    retry: None,
+                })
+            }
+        }
+    }
+
+    pub(super) fn open_form(&mut self, form: Form, values: Vec<String>) {
+        //! Switches to the form, with its inputs filled in with values
+        self.inputs = self.inputs.iter().map(|_| String::new()).collect();
+        for (input, value) in self.inputs.iter_mut().zip(values) {
+            *input = value;
+        }
+        self.input_position = 0;
+        self.mode = Mode::Form(form);
+    }
+
+    pub(super) fn handle_manage_key(&mut self, key: KeyCode) {
+        //! Key handling while a section or label list is open
+        let Mode::Manage(manage) = &self.mode else {
+            return;
+        };
+        let manage = manage.clone();
+        let rows = self.manage_rows(&manage);
+        let current = self.manage_position.selected().unwrap_or(0);
+        let selected = rows.get(current).cloned();
+        match key {
+            KeyCode::Char('j') | KeyCode::Down if current + 1 < rows.len() => {
+                self.manage_position.select(Some(current + 1))
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.manage_position.select(Some(current.saturating_sub(1)))
+            }
+
+            KeyCode::Char('a') => {
+                let form = match manage {
+                    Manage::Sections { project_id } => Form::Section {
+                        project_id,
+                        id: None,
+                    },
+                    Manage::Labels => Form::Label { id: None },
+                };
+                self.open_form(form, Vec::new())
+            }
+            KeyCode::Char('r') => {
+                let Some((id, name, color)) = selected else {
+                    return;
+                };
+                let form = match manage {
+                    Manage::Sections { project_id } => Form::Section {
+                        project_id,
+                        id: Some(id),
+                    },
+                    Manage::Labels => Form::Label { id: Some(id) },
+                };
+                self.open_form(form, vec![name, color])
+            }
+            // labels can't be archived
+            KeyCode::Char('A') if matches!(manage, Manage::Sections { .. }) => {
+                let Some((id, name, _)) = selected else {
+                    return;
+                };
+                self.confirm_command(
+                    format!("archive section \"{name}\"?"),
+                    Command::SectionArchive { id },
+                )
+            }
+            KeyCode::Char('D') => {
+                let Some((id, name, _)) = selected else {
+                    return;
+                };
+                let (question, command) = match manage {
+                    Manage::Sections { .. } => (
+                        format!("delete section \"{name}\" and all of its tasks?"),
+                        Command::SectionDelete { id },
+                    ),
+                    Manage::Labels => (
+                        format!("delete label \"{name}\" from every task?"),
+                        Command::LabelDelete { id },
+                    ),
+                };
+                self.confirm_command(question, command)
+            }
+
+            KeyCode::Esc | KeyCode::Backspace => self.mode = Mode::Normal,
+            _ => {}
+        }
+    }
+
+    pub(super) fn confirm_command(&mut self, question: String, command: Command) {
+        //! Asks before sending a command, coming back to the current mode after
+        let back = std::mem::replace(&mut self.mode, Mode::Normal);
+        self.mode = Mode::Confirm(Box::new(Confirm::Send {
+            question,
+            command,
+            back,
+        }));
+    }
+
+    pub(super) fn handle_form_key(&mut self, key: KeyCode) {
+        //! Key handling while filling in a form
+        let Mode::Form(form) = &self.mode else {
+            return;
+        };
+        let fields = form.fields().len();
+        match key {
+            KeyCode::Enter => match self.submit_form() {
+                Ok(()) => self.close_form(),
+                // stay in the form so the input can be fixed
+                Err(message) => {
+                    self.notice = Some(Notice {
+                        message,
+                        retry: None,
+                    })
+                }
+            },
+            KeyCode::Tab | KeyCode::Down => {
+                self.input_position = (self.input_position + 1) % fields
+            }
+            KeyCode::Up => self.input_position = (self.input_position + fields - 1) % fields,
+            KeyCode::Char(input_character) => {
+                self.inputs[self.input_position].push(input_character)
+            }
+            KeyCode::Backspace => _ = self.inputs[self.input_position].pop(),
+            KeyCode::Esc | KeyCode::Delete => self.close_form(),
+            _ => {}
+        }
+    }
+
+    fn close_form(&mut self) {
+        let Mode::Form(form) = &self.mode else {
+            return;
+        };
+        self.mode = form.back();
+        self.inputs = self.inputs.iter().map(|_| String::new()).collect();
+        self.input_position = 0;
+    }
+
+    fn submit_form(&mut self) -> Result<(), String> {
+        //! Sends the form as a command, or returns why the inputs are invalid
+        let Mode::Form(form) = &self.mode else {
+            return Ok(());
+        };
+        let name = self.inputs[0].trim().to_string();
+        if name.is_empty() {
+            return Err(format!("{} can't be empty", form.fields()[0].trim()));
+        }
+        let color = match self.inputs[1].trim() {
+            "" => None,
+            color if ui::todoist_color(color) == ratatui::style::Color::Reset => {
+                return Err(format!("\"{color}\" is not a todoist colour"))
+            }
+            color => Some(color.to_string()),
+        };
+        let command = form.command(name, color)?;
+        self.worker
+            .send(worker::Request::Batch(CommandBatch::new().with(command)));
+        Ok(())
+    }
+
+    pub(super) fn project_command(&mut self, key: KeyCode, project_id: Option<String>) {
+        //! Adding, editing, archiving and deleting projects from the sidebar
+        let project = project_id.and_then(|id| self.projects.iter().find(|x| x.id() == id));
+        match (key, project) {
+            (KeyCode::Char('a'), _) => self.open_form(Form::Project { id: None }, Vec::new()),
+            (KeyCode::Char('r'), Some(project)) => {
+                let values = vec![project.name.clone(), project.color.clone()];
+                let form = Form::Project {
+                    id: Some(project.id.clone()),
+                };
+                self.open_form(form, values)
+            }
+            // the inbox can't be archived or deleted
+            (KeyCode::Char('A'), Some(project)) if !project.is_inbox => {
+                let question = format!("archive project \"{}\"?", project.name);
+                let id = project.id.clone();
+                self.confirm_command(question, Command::ProjectArchive { id })
+            }
+            (KeyCode::Char('D'), Some(project)) if !project.is_inbox => {
+                let question = format!("delete project \"{}\" and all of its tasks?", project.name);
+                let id = project.id.clone();
+                self.confirm_command(question, Command::ProjectDelete { id })
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minute_offset(when: &str) -> Option<i64> {
+        match reminder_command("1", when) {
+            Ok(Command::ReminderAdd { minute_offset, .. }) => minute_offset,
+            _ => None,
+        }
+    }
+
+    fn due(when: &str) -> Option<serde_json::Value> {
+        match reminder_command("1", when) {
+            Ok(Command::ReminderAdd { due, .. }) => serde_json::to_value(due).ok(),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn relative_reminders_are_minutes_before_due() {
+        assert_eq!(minute_offset("30m"), Some(30));
+        assert_eq!(minute_offset("2h"), Some(120));
+        assert_eq!(minute_offset("1d"), Some(1440));
+        assert!(reminder_command("1", "-5m").is_err());
+        assert!(reminder_command("1", "9999999999999999d").is_err());
+    }
+
+    #[test]
+    fn absolute_reminders_need_a_real_date_and_time() {
+        let expected = serde_json::json!({"date": "2024-05-01T09:00:00"});
+        assert_eq!(due("2024-05-01 09:00"), Some(expected.clone()));
+        assert_eq!(due("2024-05-01T09:00"), Some(expected));
+        assert!(reminder_command("1", "2024-99-99 99:99").is_err());
+        assert!(reminder_command("1", "2024-02-30 09:00").is_err());
+        assert!(reminder_command("1", "tomorrow").is_err());
+    }
+}
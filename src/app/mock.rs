@@ -0,0 +1,464 @@
+//! Stand-in HTTP server for tests, answering requests on a local port
+//! with whatever the handler it was started with returns
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde_json::{json, Value};
+
+#[derive(Clone, Debug)]
+/// A request the server received
+pub struct Request {
+    pub method: String,
+    /// path without the query string, e.g. /sync
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    /// header names are lowercase
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn form(&self) -> Vec<(String, String)> {
+        //! Fields of a form encoded body
+        form_urlencoded::parse(self.body.as_bytes())
+            .into_owned()
+            .collect()
+    }
+
+    pub fn field(&self, name: &str) -> Option<String> {
+        //! A field of a form encoded body
+        self.form()
+            .into_iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        //! A query string parameter
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// What the server answers a request with
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Response {
+    pub fn json(body: impl ToString) -> Response {
+        //! 200 with a json body
+        Response {
+            status: 200,
+            headers: vec![(
+                String::from("Content-Type"),
+                String::from("application/json"),
+            )],
+            body: body.to_string(),
+        }
+    }
+
+    pub fn status(status: u16) -> Response {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: format!("status {status}"),
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Response {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// Handle to a server running on its own thread, which lives until the tests end
+pub struct MockServer {
+    /// base url to point an Api at
+    pub url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockServer {
+    pub fn start(
+        mut handler: impl FnMut(&Request) -> Option<Response> + Send + 'static,
+    ) -> MockServer {
+        //! Answers every request with what the handler returns. Returning None drops
+        //! the connection without a response, like a server that can't be reached
+        let listener = TcpListener::bind("127.0.0.1:0").expect("a local port is free");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = Arc::clone(&requests);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let Some(request) = read_request(&mut stream) else {
+                    continue;
+                };
+                received.lock().unwrap().push(request.clone());
+                if let Some(response) = handler(&request) {
+                    write_response(&mut stream, response);
+                }
+            }
+        });
+        MockServer { url, requests }
+    }
+
+    pub fn scripted(responses: Vec<Response>) -> MockServer {
+        //! Answers requests with the responses in order, then with 500s once they run out
+        let mut responses = responses.into_iter();
+        MockServer::start(move |_| Some(responses.next().unwrap_or(Response::status(500))))
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        //! Every request received so far, oldest first
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// (id, name) of every project the stand-in has
+const PROJECTS: [(&str, &str); 2] = [("inbox", "Inbox"), ("work", "Work")];
+
+/// In-memory stand-in for the Todoist sync endpoint, keeping just enough
+/// state to run the app against end to end
+pub struct Todoist {
+    /// every item ever added, alongside the version it last changed in
+    items: Vec<(u64, Value)>,
+    /// bumped by every write, sync tokens are a version number
+    version: u64,
+    next_id: u64,
+    /// types of command to reject, e.g. "item_close"
+    pub rejects: Vec<&'static str>,
+    /// drops every request while set, as if the server couldn't be reached
+    pub offline: bool,
+    /// how many of the next requests to handle without sending a response back
+    pub lost_responses: usize,
+    /// answers every write with this status instead of applying it, while set
+    pub failing_writes: Option<u16>,
+    /// X-Request-Id of every quick add, alongside the id of the item it added
+    request_ids: HashMap<String, String>,
+}
+
+impl Todoist {
+    pub fn new() -> Todoist {
+        Todoist {
+            items: Vec::new(),
+            version: 1,
+            next_id: 1,
+            rejects: Vec::new(),
+            offline: false,
+            lost_responses: 0,
+            failing_writes: None,
+            request_ids: HashMap::new(),
+        }
+    }
+
+    pub fn start(self) -> (MockServer, Arc<Mutex<Todoist>>) {
+        //! Serves the state on a local port, the handle can change and check it meanwhile
+        let todoist = Arc::new(Mutex::new(self));
+        let state = Arc::clone(&todoist);
+        let server = MockServer::start(move |request| {
+            let mut todoist = state.lock().unwrap();
+            if todoist.offline {
+                return None;
+            }
+            let response = todoist.handle(request);
+            match todoist.lost_responses {
+                0 => Some(response),
+                _ => {
+                    todoist.lost_responses -= 1;
+                    None
+                }
+            }
+        });
+        (server, todoist)
+    }
+
+    pub fn add(&mut self, item: Value) -> String {
+        //! Adds an item as if from another client, filling in any fields left out
+        let id = self.next_id.to_string();
+        self.next_id += 1;
+        let mut full = json!({
+            "id": id,
+            "content": "",
+            "description": "",
+            "checked": false,
+            "is_deleted": false,
+            "labels": [],
+            "priority": 1,
+            "due": null,
+            "project_id": "inbox",
+            "section_id": null,
+            "parent_id": null,
+            "child_order": 0,
+            "duration": null,
+            "deadline": null,
+        });
+        for (key, value) in item.as_object().into_iter().flatten() {
+            full[key] = value.clone();
+        }
+        self.version += 1;
+        self.items.push((self.version, full));
+        id
+    }
+
+    pub fn item(&self, id: &str) -> Option<&Value> {
+        self.items
+            .iter()
+            .map(|(_, item)| item)
+            .find(|item| item["id"] == id)
+    }
+
+    fn handle(&mut self, request: &Request) -> Response {
+        if request.path == "/tasks/quick" {
+            // a repeated request id gets the item it already added
+            let request_id = request.header("x-request-id").map(String::from);
+            if let Some(id) = request_id.as_ref().and_then(|x| self.request_ids.get(x)) {
+                return Response::json(self.item(id).unwrap());
+            }
+            let text: Value = serde_json::from_str(&request.body).unwrap();
+            let id = self.quick_add(text["text"].as_str().unwrap());
+            if let Some(request_id) = request_id {
+                self.request_ids.insert(request_id, id.clone());
+            }
+            return Response::json(self.item(&id).unwrap());
+        }
+        if request.path != "/sync" {
+            return Response::status(404);
+        }
+        match (request.field("commands"), request.field("sync_token")) {
+            (Some(_), _) if self.failing_writes.is_some() => {
+                Response::status(self.failing_writes.unwrap())
+            }
+            (Some(commands), _) => self.write(serde_json::from_str(&commands).unwrap()),
+            (None, Some(sync_token)) => self.read(&sync_token),
+            (None, None) => Response::status(400),
+        }
+    }
+
+    fn quick_add(&mut self, text: &str) -> String {
+        //! Understands priorities, labels and projects, anything else is the content
+        let mut item = json!({"labels": []});
+        let mut content = Vec::new();
+        for word in text.split_whitespace() {
+            match (word.chars().next(), word.get(1..)) {
+                (Some('p'), Some(level @ ("1" | "2" | "3" | "4"))) => {
+                    item["priority"] = json!(5 - level.parse::<u8>().unwrap())
+                }
+                (Some('@'), Some(label)) => {
+                    item["labels"].as_array_mut().unwrap().push(json!(label))
+                }
+                (Some('#'), Some(project)) => {
+                    item["project_id"] = PROJECTS
+                        .iter()
+                        .find(|(_, name)| name == &project)
+                        .map(|(id, _)| json!(id))
+                        .unwrap_or(json!("inbox"))
+                }
+                _ => content.push(word),
+            }
+        }
+        item["content"] = json!(content.join(" "));
+        self.add(item)
+    }
+
+    fn read(&self, sync_token: &str) -> Response {
+        //! Everything active for "*", otherwise whatever changed after that version
+        let full_sync = sync_token == "*";
+        let since: u64 = sync_token.parse().unwrap_or(0);
+        let items: Vec<&Value> = self
+            .items
+            .iter()
+            .filter(|(version, item)| match full_sync {
+                true => item["checked"] == false && item["is_deleted"] == false,
+                false => *version > since,
+            })
+            .map(|(_, item)| item)
+            .collect();
+        let mut data = json!({
+            "full_sync": full_sync,
+            "sync_token": self.version.to_string(),
+            "items": items,
+        });
+        if full_sync {
+            data["projects"] = PROJECTS
+                .iter()
+                .map(|(id, name)| {
+                    json!({"id": id, "name": name, "parent_id": null, "inbox_project": *id == "inbox"})
+                })
+                .collect();
+            data["user"] = json!({"id": "user", "full_name": "Someone"});
+        }
+        Response::json(data)
+    }
+
+    fn write(&mut self, commands: Vec<Value>) -> Response {
+        //! Applies the commands in order, each one succeeding or failing on its own.
+        //! The sync token given back is from before them, so the next sync fetches
+        //! what they changed
+        // like the real endpoint, which takes at most 100 commands at once
+        if commands.len() > 100 {
+            return Response::status(400);
+        }
+        let sync_token = self.version.to_string();
+        let mut statuses = serde_json::Map::new();
+        let mut temp_ids = serde_json::Map::new();
+        for command in commands {
+            let status = match self.apply(&command, &mut temp_ids) {
+                Ok(()) => json!("ok"),
+                Err(error) => json!({"error_code": 22, "error": error}),
+            };
+            statuses.insert(command["uuid"].as_str().unwrap().to_string(), status);
+        }
+        Response::json(json!({
+            "sync_token": sync_token,
+            "sync_status": statuses,
+            "temp_id_mapping": temp_ids,
+        }))
+    }
+
+    fn apply(
+        &mut self,
+        command: &Value,
+        temp_ids: &mut serde_json::Map<String, Value>,
+    ) -> Result<(), String> {
+        if self.rejects.iter().any(|kind| command["type"] == *kind) {
+            return Err(String::from("Invalid argument value"));
+        }
+        let mut args = command["args"].clone();
+        // ids added earlier in the same request can be referred to by temp id
+        for key in ["id", "parent_id"] {
+            if let Some(real_id) = args[key].as_str().and_then(|id| temp_ids.get(id)) {
+                args[key] = real_id.clone();
+            }
+        }
+        if command["type"] == "item_add" {
+            if let Some(due) = args.get_mut("due") {
+                *due = parsed_due(due);
+            }
+            let id = self.add(args);
+            if let Some(temp_id) = command["temp_id"].as_str() {
+                temp_ids.insert(temp_id.to_string(), json!(id));
+            }
+            return Ok(());
+        }
+
+        let version = self.version + 1;
+        let Some((changed, item)) = self
+            .items
+            .iter_mut()
+            .find(|(_, item)| item["id"] == args["id"] && item["is_deleted"] == false)
+        else {
+            return Err(String::from("Item not found"));
+        };
+        match command["type"].as_str().unwrap() {
+            "item_close" => item["checked"] = json!(true),
+            "item_uncomplete" => item["checked"] = json!(false),
+            "item_delete" => item["is_deleted"] = json!(true),
+            "item_update" => {
+                for (key, value) in args.as_object().unwrap() {
+                    item[key] = match key.as_str() {
+                        "due" => parsed_due(value),
+                        _ => value.clone(),
+                    };
+                }
+            }
+            "item_move" => {
+                if let Some(project_id) = args.get("project_id") {
+                    item["project_id"] = project_id.clone();
+                    item["section_id"] = Value::Null;
+                }
+                if let Some(section_id) = args.get("section_id") {
+                    item["section_id"] = section_id.clone();
+                }
+                item["parent_id"] = args.get("parent_id").cloned().unwrap_or(Value::Null);
+            }
+            other => return Err(format!("{other} isn't supported")),
+        }
+        *changed = version;
+        self.version = version;
+        Ok(())
+    }
+}
+
+fn parsed_due(due: &Value) -> Value {
+    //! Due as the server would store it. Only exact dates are understood,
+    //! a due string is taken to be the date it stands for
+    match due {
+        Value::Null => Value::Null,
+        due => {
+            let date = due.get("date").or(due.get("string")).cloned();
+            let string = due.get("string").or(due.get("date")).cloned();
+            json!({"date": date, "string": string, "is_recurring": false})
+        }
+    }
+}
+
+fn read_request(stream: &mut TcpStream) -> Option<Request> {
+    //! Reads a request line, headers and a body of the length given in Content-Length
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?.to_string();
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':')?;
+        headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+    }
+    let length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    Some(Request {
+        method,
+        path: path.to_string(),
+        query: form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect(),
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+fn write_response(stream: &mut TcpStream, response: Response) {
+    let mut head = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        response.body.len()
+    );
+    for (name, value) in response.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str("\r\n");
+    let _ = stream.write_all(head.as_bytes());
+    let _ = stream.write_all(response.body.as_bytes());
+}
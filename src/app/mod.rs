@@ -1,20 +1,149 @@
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
-use ratatui::widgets::*;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::{layout::Rect, widgets::*};
+
+use crate::config::Config;
 use crate::tui;
 mod api;
+mod cache;
+mod chooser;
+mod completed;
+mod info;
+mod labels;
+mod manage;
+#[cfg(test)]
+mod mock;
+mod outbox;
+mod projects;
+#[cfg(test)]
+mod tests;
+mod tree;
 mod ui;
+mod undo;
+mod view;
+mod worker;
+
+use api::Resource;
+
+pub use api::{ApiError, DueFormat, RetryPolicy};
+
+#[derive(Debug)]
+/// Errors that stop the app's main loop
+pub enum Error {
+    /// A request to the Todoist API failed
+    Api(ApiError),
+    /// Crossterm failed to read an event from the terminal
+    Terminal(std::io::Error),
+}
+
+impl From<ApiError> for Error {
+    fn from(error: ApiError) -> Error {
+        Error::Api(error)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Api(error) => write!(f, "{error}"),
+            Error::Terminal(error) => write!(f, "could not read terminal event: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Api(error) => Some(error),
+            Error::Terminal(error) => Some(error),
+        }
+    }
+}
 
 /// App client struct containing all app state variables
 pub struct App {
-    client: api::Api,
+    worker: worker::Worker,
+    /// base url of the API, the cache is only used with the one it was synced from
+    api_url: String,
+    /// identifies the account the token is for, as does the cache
+    account: String,
+    /// where synced state is saved between runs, None if there is no home directory
+    cache_path: Option<PathBuf>,
+    /// the last request failed to reach the server, tasks shown may be out of date
+    offline: bool,
+    /// task changes waiting to be sent, oldest first
+    outbox: Vec<api::SyncCommand>,
+    /// the outbox has been sent and the response hasn't come back yet
+    replaying: bool,
+    /// when to next check if the server is back while offline
+    reconnect_at: Instant,
+    /// selected row of the task list, an index into rows
     position: ListState,
     tasks: Vec<api::Task>,
+    /// rows of the task list as currently displayed, headings and tasks
+    rows: Vec<view::Row>,
+    /// keys of task list headings and parent tasks that are folded
+    folded: HashSet<String>,
+    projects: Vec<api::Project>,
+    sections: Vec<api::Section>,
+    labels: Vec<api::Label>,
+    notes: Vec<api::Note>,
+    reminders: Vec<api::Reminder>,
+    user: Option<api::User>,
+    collaborators: Vec<api::Collaborator>,
+    /// id of the reminder or comment picked in the info pane
+    info_selected: Option<String>,
+    /// selected row of the section or label list being managed
+    manage_position: ListState,
+    /// selected row of the project sidebar
+    project_position: ListState,
+    /// ids of projects with their sub-projects hidden in the sidebar
+    collapsed_projects: HashSet<String>,
+    /// only tasks in this project are listed, all tasks if None
+    project_filter: Option<String>,
+    /// only tasks with every one of these labels are listed
+    label_filter: Vec<String>,
+    /// only tasks with a deadline are listed
+    deadline_filter: bool,
+    /// list tasks by deadline rather than priority
+    sort_by_deadline: bool,
     current_sync_token: String,
     mode: Mode,
     inputs: Vec<String>,
     input_position: usize,
+    /// parent edit mode has indented or outdented the task to, None if unchanged
+    edit_parent: Option<Option<String>>,
     exit: bool,
+    /// shown in the status bar until dismissed
+    notice: Option<Notice>,
+    /// shown in the status bar for a few seconds after it was set, unless there is a notice
+    toast: Option<(String, Instant)>,
+    /// changes that can be undone, newest last
+    undo_stack: Vec<undo::Undo>,
+    /// tasks completed in the week shown by the completed view, newest first
+    completed: Vec<api::CompletedTask>,
+    /// weeks back from today the completed view shows
+    completed_page: u64,
+    /// selected row of the completed view
+    completed_position: ListState,
+    /// how due dates are shown
+    due_format: api::DueFormat,
+    /// counts redraws, used to animate the spinner while requests are in flight
+    ticks: usize,
+}
+
+/// How long a toast stays in the status bar
+const TOAST_DURATION: Duration = Duration::from_secs(4);
+
+/// A recoverable problem shown in the status bar
+struct Notice {
+    message: String,
+    /// request that failed, sent again if the user chooses to retry
+    retry: Option<worker::Request>,
 }
 
 enum Mode {
@@ -22,82 +151,382 @@ enum Mode {
     Create,
     Info,
     Edit,
+    /// project sidebar has focus
+    Projects,
+    /// waiting for a yes or no answer
+    Confirm(Box<tree::Confirm>),
+    /// list of sections or labels has focus
+    Manage(manage::Manage),
+    /// filling in a form to add or change a project, section, label or comment
+    Form(manage::Form),
+    /// choosing labels for the task being edited or to filter by
+    PickLabels(labels::Picker),
+    /// choosing where to move or duplicate the selected task to
+    Choose(chooser::Chooser),
+    /// tasks completed recently, a week at a time
+    Completed,
 }
 
 impl App {
-    pub fn new(todoist_token: String) -> App {
+    pub fn new(todoist_token: String, config: Config) -> App {
         //! Returns a newly created App struct, including initiating the API client.
-        //! Consumes a String that is the API Token for the Todoist API.
+        //! Consumes a String that is the API Token for the Todoist API, and the loaded Config.
+        let api_url = config.api_url();
+        let account = cache::account_key(&todoist_token);
         App {
-            client: api::Api::new(todoist_token),
+            worker: worker::Worker::spawn(
+                api::Api::new(todoist_token, api_url.clone()).with_retry_policy(config.retry),
+            ),
+            api_url,
+            account,
+            cache_path: cache::cache_path(),
+            offline: false,
+            outbox: Vec::new(),
+            replaying: false,
+            reconnect_at: Instant::now(),
             position: ListState::default(),
             tasks: Vec::new(),
+            rows: Vec::new(),
+            folded: HashSet::new(),
+            projects: Vec::new(),
+            sections: Vec::new(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            reminders: Vec::new(),
+            user: None,
+            collaborators: Vec::new(),
+            info_selected: None,
+            manage_position: ListState::default(),
+            project_position: ListState::default().with_selected(Some(0)),
+            collapsed_projects: HashSet::new(),
+            project_filter: None,
+            label_filter: Vec::new(),
+            deadline_filter: false,
+            sort_by_deadline: false,
             mode: Mode::Normal,
             current_sync_token: String::from("*"),
-            inputs: vec![
-                String::new(),
-                String::new(),
-                String::new(),
-                String::new(),
-                String::new(),
-            ],
+            inputs: vec![String::new(); 7],
             input_position: 0,
+            edit_parent: None,
             exit: false,
+            notice: None,
+            toast: None,
+            undo_stack: Vec::new(),
+            completed: Vec::new(),
+            completed_page: 0,
+            completed_position: ListState::default(),
+            due_format: config.due,
+            ticks: 0,
         }
     }
 
-    pub fn run(&mut self, terminal: &mut tui::Tui) -> Result<(), u16> {
+    pub fn run(&mut self, terminal: &mut tui::Tui) -> Result<(), Error> {
         //! Starts the main loop for the app, returning an empty result.
         //! Takes a &mut tui::Tui used to render the UI.
         //! ```
-        //! let mut app = App::new(token);
+        //! let config = Config::load()?;
+        //! let mut app = App::new(token, config);
         //! let app_result = app.run(terminal);
         //! ```
-        (self.tasks, self.current_sync_token) = self.client.get_tasks("*")?;
-        self.decrement_selection();
+        // show whatever was saved last time straight away, then fetch what changed since
+        self.load_cache();
+        self.replay_outbox();
+        self.sync_tasks();
         while !self.exit {
+            self.handle_responses()?;
+            self.poll_reconnect();
+            if self
+                .toast
+                .as_ref()
+                .is_some_and(|(_, shown)| shown.elapsed() > TOAST_DURATION)
+            {
+                self.toast = None;
+            }
             // calls the ui module to create and render widgets
-            let _ = terminal.draw(|frame| self.render_frame(frame));
+            let _ = terminal.draw(|frame| {
+                let show_status = self.notice.is_some() || self.toast.is_some();
+                let (area, status_area) = ui::split_status(frame.size(), show_status);
+                self.render_frame(frame, area);
+                if let Some(notice) = &self.notice {
+                    // a replay that keeps failing would otherwise be stuck in the outbox
+                    let can_discard = matches!(notice.retry, Some(worker::Request::Replay(_)));
+                    ui::render_status(
+                        frame,
+                        status_area,
+                        &notice.message,
+                        notice.retry.is_some(),
+                        can_discard,
+                    );
+                } else if let Some((message, _)) = &self.toast {
+                    ui::render_toast(frame, status_area, message);
+                }
+                if self.worker.is_busy() {
+                    ui::render_spinner(frame, area, self.ticks);
+                }
+            });
+            self.ticks = self.ticks.wrapping_add(1);
             self.handle_events()?;
         }
+        // nowhere left to report a failed save, the next run just syncs more
+        let _ = self.save_cache();
+        Ok(())
+    }
+
+    fn handle_responses(&mut self) -> Result<(), Error> {
+        //! Applies the results of any requests the worker has finished
+        while let Some(response) = self.worker.try_recv() {
+            match response {
+                worker::Response::Synced(requested_token, result) => {
+                    let retry = worker::Request::Sync(requested_token.clone());
+                    let Some(data) = self.check_result(result, retry)? else {
+                        continue;
+                    };
+                    self.apply_sync(data);
+                    self.persist();
+                    // the server can be reached, so send anything made while it couldn't
+                    self.replay_outbox();
+                }
+                worker::Response::Replayed(sent, result) => self.replayed(sent, result)?,
+                worker::Response::Added(request_id, text, result) => {
+                    self.quick_added(request_id, text, result)?
+                }
+                worker::Response::FetchedCompleted(since, until, result) => {
+                    self.fetched_completed(since, until, result)?
+                }
+                worker::Response::Batched(batch, result) => {
+                    let retry = worker::Request::Batch(batch);
+                    let Some(sync_token) = self.check_result(result, retry)? else {
+                        continue;
+                    };
+                    self.current_sync_token = sync_token;
+                    // batches can touch many tasks, so fetch what changed
+                    self.sync_tasks();
+                }
+            }
+        }
         Ok(())
     }
 
+    fn apply_sync(&mut self, data: api::SyncData) {
+        //! Merges synced resources into the app's state, from the server or the cache
+        api::merge(&mut self.projects, data.projects, data.full_sync);
+        api::merge(&mut self.sections, data.sections, data.full_sync);
+        api::merge(&mut self.labels, data.labels, data.full_sync);
+        api::merge(&mut self.notes, data.notes, data.full_sync);
+        api::merge(&mut self.reminders, data.reminders, data.full_sync);
+        api::merge(&mut self.collaborators, data.collaborators, data.full_sync);
+        if data.user.is_some() {
+            self.user = data.user;
+        }
+        if self
+            .project_filter
+            .as_ref()
+            .is_some_and(|id| !self.projects.iter().any(|x| x.id() == id))
+        {
+            self.project_filter = None;
+        }
+        self.update_tasks(|tasks| api::merge(tasks, data.items, data.full_sync));
+        self.current_sync_token = data.sync_token;
+    }
+
+    fn check_result<T>(
+        &mut self,
+        result: Result<T, ApiError>,
+        retry: worker::Request,
+    ) -> Result<Option<T>, Error> {
+        //! Error policy for API requests. Auth failures are fatal and end the app,
+        //! rejected commands are reported and everything fully resynced, requests that
+        //! weren't allowed are reported, anything else (network, http or parse errors)
+        //! is reported with the option to retry
+        self.offline = matches!(result, Err(ApiError::Network { .. }));
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(error) if error.is_auth() => Err(error.into()),
+            Err(ApiError::Rejected { failures, .. }) => {
+                self.notice = Some(Notice {
+                    message: failures
+                        .iter()
+                        .map(|(command, error)| format!("{command} rejected: {error}"))
+                        .collect::<Vec<String>>()
+                        .join(", "),
+                    retry: None,
+                });
+                self.resync_tasks();
+                Ok(None)
+            }
+            // retrying won't change the answer, so the notice can only be dismissed
+            Err(error) if error.status() == Some(403) => {
+                self.notice = Some(Notice {
+                    message: error.to_string().replace('\n', " "),
+                    retry: None,
+                });
+                Ok(None)
+            }
+            Err(error) => {
+                self.notice = Some(Notice {
+                    message: error.to_string().replace('\n', " "),
+                    retry: Some(retry),
+                });
+                Ok(None)
+            }
+        }
+    }
+
     // renders the task list widget
-    fn render_frame(&mut self, frame: &mut ratatui::Frame) {
-        let tasks = &self
-            .tasks
-            .iter()
-            .map(|task| task.to_list_string(frame.size().width))
-            .collect();
+    fn render_frame(&mut self, frame: &mut ratatui::Frame, area: Rect) {
+        // project sidebar is shown alongside the task list in normal mode,
+        // and under any popups
+        let with_sidebar = matches!(
+            self.mode,
+            Mode::Normal
+                | Mode::Projects
+                | Mode::Confirm(_)
+                | Mode::Manage(_)
+                | Mode::Form(_)
+                | Mode::Choose(_)
+                | Mode::PickLabels(labels::Picker {
+                    purpose: labels::Purpose::Filter,
+                    ..
+                })
+        );
+        let (sidebar_area, area) = match with_sidebar {
+            true => ui::split_sidebar(area),
+            false => (Rect::default(), area),
+        };
+        let mut title = match &self.project_filter {
+            Some(id) => format!(" {} ", self.project_name(id)),
+            None => String::from(" todo "),
+        };
+        for label in &self.label_filter {
+            title.push_str(&format!("@{label} "));
+        }
+        if self.deadline_filter {
+            title.push_str("with deadline ");
+        }
+        if self.sort_by_deadline {
+            title.push_str("by deadline ");
+        }
+        if self.offline {
+            title.push_str("(offline) ");
+        }
+        let tasks = &self.rows_text(area.width);
 
-        match self.mode {
-            // normal mode just displays the task list
-            Mode::Normal => ui::render_normal_ui(frame, tasks, &mut self.position),
+        match &self.mode {
+            // normal mode displays the task list and project sidebar
+            Mode::Normal
+            | Mode::Projects
+            | Mode::Confirm(_)
+            | Mode::Manage(_)
+            | Mode::Form(_)
+            | Mode::Choose(_)
+            | Mode::PickLabels(labels::Picker {
+                purpose: labels::Purpose::Filter,
+                ..
+            }) => {
+                let focused = matches!(self.mode, Mode::Projects);
+                let rows = self.project_rows_text();
+                ui::render_projects(
+                    frame,
+                    sidebar_area,
+                    &rows,
+                    &mut self.project_position,
+                    focused,
+                );
+                ui::render_normal_ui(frame, area, tasks, &mut self.position, &title);
+                match &self.mode {
+                    Mode::Confirm(confirm) => ui::render_confirm(frame, area, &confirm.question()),
+                    Mode::Manage(manage) => {
+                        let rows: Vec<(String, String)> = self
+                            .manage_rows(manage)
+                            .into_iter()
+                            .map(|(_, name, color)| (name, color))
+                            .collect();
+                        let can_archive = matches!(manage, manage::Manage::Sections { .. });
+                        ui::render_manage(
+                            frame,
+                            area,
+                            &self.manage_title(manage),
+                            &rows,
+                            &mut self.manage_position,
+                            can_archive,
+                        )
+                    }
+                    Mode::Form(form) => {
+                        let fields: Vec<(&str, &str)> = form
+                            .fields()
+                            .iter()
+                            .zip(&self.inputs)
+                            .map(|(title, input)| (*title, input.as_str()))
+                            .collect();
+                        ui::render_form(frame, area, form.title(), &fields)
+                    }
+                    Mode::PickLabels(picker) => {
+                        let rows = self.picker_rows(picker);
+                        ui::render_picker(frame, area, &picker.query, &rows, picker.position)
+                    }
+                    Mode::Choose(chooser) => {
+                        let rows: Vec<String> = self
+                            .chooser_rows(chooser)
+                            .into_iter()
+                            .map(|(text, _)| text)
+                            .collect();
+                        ui::render_chooser(frame, area, &chooser.title(), &rows, chooser.position)
+                    }
+                    _ => {}
+                }
+            }
+
+            Mode::Completed => {
+                let rows = self.completed_rows_text();
+                ui::render_completed(
+                    frame,
+                    area,
+                    &self.completed_title(),
+                    &rows,
+                    &mut self.completed_position,
+                )
+            }
 
             // create task mode
-            Mode::Create => ui::render_create_ui(frame, tasks, &mut self.position, &self.inputs[0]),
+            Mode::Create => ui::render_create_ui(
+                frame,
+                area,
+                tasks,
+                &mut self.position,
+                &title,
+                &self.inputs[0],
+            ),
             Mode::Info => {
-                let taskinfo = match self.position.selected() {
-                    Some(index) => self.tasks[index].to_info_string(),
+                let taskinfo = match self.selected_task() {
+                    Some(index) => self.info_text(index),
                     None => {
                         self.mode = Mode::Normal;
                         return;
                     }
                 };
-                ui::render_info_ui(frame, tasks, &mut self.position, taskinfo)
+                ui::render_info_ui(frame, area, tasks, &mut self.position, &title, taskinfo)
             }
 
             // edit mode to edit currently selected task
-            Mode::Edit => {
-                let (_, title, description, labels, date, priority) = match self.position.selected()
-                {
-                    Some(index) => self.tasks[index].get_details(),
-                    None => {
-                        self.mode = Mode::Normal;
-                        return;
-                    }
+            Mode::Edit
+            | Mode::PickLabels(labels::Picker {
+                purpose: labels::Purpose::Edit,
+                ..
+            }) => {
+                let Some(index) = self.selected_task() else {
+                    self.mode = Mode::Normal;
+                    return;
+                };
+                let (_, task_title, description, labels, date, priority) =
+                    self.tasks[index].get_details();
+                let parent_id = match &self.edit_parent {
+                    Some(pending) => pending.as_deref(),
+                    None => self.tasks[index].parent_id(),
                 };
+                let parent = parent_id
+                    .and_then(|id| self.tasks.iter().find(|x| x.id() == id))
+                    .map(|x| x.content().to_string())
+                    .unwrap_or(String::from("none"));
                 if self
                     .inputs
                     .iter()
@@ -105,38 +534,48 @@ impl App {
                     .reduce(|a, b| a & b)
                     .unwrap()
                 {
+                    let task = &self.tasks[index];
                     self.inputs = vec![
-                        title,
+                        task_title,
                         description,
                         labels.join(", "),
                         date,
                         format!("{}", priority),
+                        task.duration_string(),
+                        task.deadline_date().unwrap_or_default().to_string(),
                     ]
                 };
-                ui::render_edit_ui(
-                    frame,
-                    &self.inputs[0],
-                    &self.inputs[1],
-                    &self.inputs[2],
-                    &self.inputs[3],
-                    &self.inputs[4],
-                )
+                ui::render_edit_ui(frame, area, &self.inputs, &parent);
+                if let Mode::PickLabels(picker) = &self.mode {
+                    let rows = self.picker_rows(picker);
+                    ui::render_picker(frame, area, &picker.query, &rows, picker.position)
+                }
             }
         }
     }
 
-    fn handle_events(&mut self) -> Result<(), u16> {
+    fn handle_events(&mut self) -> Result<(), Error> {
+        // wait briefly for input so responses and the spinner still get drawn
+        match event::poll(Duration::from_millis(100)) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(error) => return Err(Error::Terminal(error)),
+        }
         match event::read() {
             Ok(Event::Key(key_event)) if key_event.kind == KeyEventKind::Press => {
-                self.handle_key_event(key_event)?
+                self.handle_key_event(key_event)
             }
             Ok(_) => {}
-            Err(_) => return Err(4),
+            Err(error) => return Err(Error::Terminal(error)),
         };
         Ok(())
     }
 
-    fn handle_key_event(&mut self, key_event: KeyEvent) -> Result<(), u16> {
+    fn handle_key_event(&mut self, key_event: KeyEvent) {
+        // reminder and comment actions take priority in the info pane
+        if matches!(self.mode, Mode::Info) && self.handle_info_key(key_event.code) {
+            return;
+        }
         match self.mode {
             Mode::Normal | Mode::Info => match key_event.code {
                 KeyCode::Char('q') => self.exit = true,
@@ -148,12 +587,35 @@ impl App {
                 KeyCode::Char('k') => self.decrement_selection(),
                 KeyCode::Up => self.decrement_selection(),
 
-                KeyCode::Char('U') => {
-                    self.current_sync_token = String::from("*");
-                    self.sync_tasks()?
+                // fetch what changed, or everything to start over from the server's state
+                KeyCode::Char('U') => self.sync_tasks(),
+                KeyCode::Char('u') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.resync_tasks()
+                }
+
+                KeyCode::Char('c') => self.complete_current_task(),
+
+                KeyCode::Char('u') => self.undo(),
+                KeyCode::Char('z') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.undo()
                 }
 
-                KeyCode::Char('c') => self.complete_current_task()?,
+                // status bar actions
+                KeyCode::Char('r') => match self.notice.take().and_then(|notice| notice.retry) {
+                    // the outbox may have changed or be on its way already, so it is
+                    // sent as it is now rather than as it was when it failed
+                    Some(worker::Request::Replay(_)) => self.replay_outbox(),
+                    Some(request) => self.worker.send(request),
+                    None => {}
+                },
+                KeyCode::Char('x') => self.notice = None,
+                KeyCode::Char('X')
+                    if self.notice.as_ref().is_some_and(|notice| {
+                        matches!(notice.retry, Some(worker::Request::Replay(_)))
+                    }) =>
+                {
+                    self.discard_outbox()
+                }
 
                 KeyCode::Enter => self.mode = Mode::Info,
 
@@ -161,13 +623,53 @@ impl App {
 
                 KeyCode::Char('e') => self.mode = Mode::Edit,
 
+                KeyCode::Char('p') => self.mode = Mode::Projects,
+
+                // manage the sections of the current project, or labels
+                KeyCode::Char('S') => self.open_sections(),
+                KeyCode::Char('L') => self.open_manage(manage::Manage::Labels),
+
+                // move, duplicate or delete the selected task
+                KeyCode::Char('m') => self.open_chooser(chooser::Action::Move),
+                KeyCode::Char('y') => self.open_chooser(chooser::Action::Duplicate),
+                KeyCode::Char('D') => self.confirm_delete(),
+
+                // recently completed tasks, to look back on or restore
+                KeyCode::Char('C') => self.open_completed(),
+
+                // only list tasks with the chosen labels
+                KeyCode::Char('@') => self.open_picker(labels::Purpose::Filter),
+
+                // sort by deadline instead of priority, or only list tasks with one
+                KeyCode::Char('s') => {
+                    self.sort_by_deadline = !self.sort_by_deadline;
+                    self.update_tasks(|_| {});
+                }
+                KeyCode::Char('d') => {
+                    self.deadline_filter = !self.deadline_filter;
+                    let selected_id = self.selected_task().map(|index| self.tasks[index].get_id());
+                    self.refresh_view(selected_id);
+                }
+
+                // fold the heading of the selected task, unfold the one next to it,
+                // or unfold all of them
+                KeyCode::Char(' ') => self.fold_selected(),
+                // fold and unfold sub-tasks
+                KeyCode::Char('h') | KeyCode::Left => self.fold_task(true),
+                KeyCode::Char('l') | KeyCode::Right => self.fold_task(false),
+                KeyCode::Char('Z') => {
+                    self.folded.clear();
+                    let selected_id = self.selected_task().map(|index| self.tasks[index].get_id());
+                    self.refresh_view(selected_id);
+                }
+
                 KeyCode::Backspace => self.mode = Mode::Normal,
                 _ => {}
             },
             // mode to allow typing for input
             Mode::Create => match key_event.code {
                 KeyCode::Enter => {
-                    self.add_task()?;
+                    self.add_task();
                     self.mode = Mode::Normal
                 }
                 // transmitts any character types to the input attribute
@@ -177,16 +679,32 @@ impl App {
                 KeyCode::Delete => self.mode = Mode::Normal,
                 _ => {}
             },
+            Mode::Projects => self.handle_projects_key(key_event.code),
+            Mode::Confirm(_) => self.handle_confirm_key(key_event.code),
+            Mode::Manage(_) => self.handle_manage_key(key_event.code),
+            Mode::Form(_) => self.handle_form_key(key_event.code),
+            Mode::PickLabels(_) => self.handle_picker_key(key_event.code),
+            Mode::Choose(_) => self.handle_chooser_key(key_event.code),
+            Mode::Completed => self.handle_completed_key(key_event.code),
             Mode::Edit => match key_event.code {
-                KeyCode::Enter => {
-                    self.edit_task()?;
-                    self.inputs = self.inputs.iter().map(|_| String::new()).collect();
-                    self.input_position = 0;
-                    self.mode = Mode::Normal;
-                }
+                KeyCode::Enter => match self.edit_task() {
+                    Ok(()) => {
+                        self.inputs = self.inputs.iter().map(|_| String::new()).collect();
+                        self.input_position = 0;
+                        self.edit_parent = None;
+                        self.mode = Mode::Normal;
+                    }
+                    // stay in edit mode so the input can be fixed
+                    Err(message) => {
+                        self.notice = Some(Notice {
+                            message,
+                            retry: None,
+                        })
+                    }
+                },
 
                 KeyCode::Tab | KeyCode::Down => {
-                    if self.input_position == 4 {
+                    if self.input_position == self.inputs.len() - 1 {
                         self.input_position = 0
                     } else {
                         self.input_position += 1
@@ -195,12 +713,29 @@ impl App {
                 // SHIFT is not working idk why i cant be asked sry
                 KeyCode::Up => {
                     if self.input_position == 0 {
-                        self.input_position = 4
+                        self.input_position = self.inputs.len() - 1
                     } else {
                         self.input_position -= 1
                     }
                 }
 
+                // indent under the task above, or outdent to the parent's level
+                KeyCode::Left | KeyCode::Right
+                    if key_event.modifiers.contains(KeyModifiers::CONTROL) =>
+                {
+                    let indent = key_event.code == KeyCode::Right;
+                    if let Some(parent) = self
+                        .selected_task()
+                        .and_then(|index| self.indent_parent(index, indent))
+                    {
+                        self.edit_parent = Some(parent)
+                    }
+                }
+
+                KeyCode::Char('l') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.open_picker(labels::Purpose::Edit)
+                }
+
                 // transmitts any character types to the input attribute
                 KeyCode::Char(input_character) => {
                     self.inputs[self.input_position].push(input_character)
@@ -210,122 +745,264 @@ impl App {
                 KeyCode::Delete | KeyCode::Esc => {
                     self.inputs = self.inputs.iter().map(|_| String::new()).collect();
                     self.input_position = 0;
+                    self.edit_parent = None;
                     self.mode = Mode::Normal;
                 }
                 _ => {}
             },
         };
-        Ok(())
     }
 
-    /// selection interaction
+    /// selection interaction, only task rows can be selected and headings are skipped
+    fn selected_task(&self) -> Option<usize> {
+        //! Index into tasks of the selected task
+        self.position
+            .selected()
+            .and_then(|row| self.rows.get(row))
+            .and_then(|row| row.task())
+    }
+
     fn increment_selection(&mut self) {
-        if self.tasks.is_empty() {
-            self.position.select(None);
-            return;
-        }
         let current = self.position.selected().unwrap_or(0);
-        let length = self.tasks.len();
-        if current == length - 1 {
-            return;
+        match (current + 1..self.rows.len()).find(|&row| self.rows[row].task().is_some()) {
+            Some(row) => self.position.select(Some(row)),
+            None => self.snap_selection(),
         }
-        self.position.select(Some(current + 1));
     }
     fn decrement_selection(&mut self) {
-        if self.tasks.is_empty() {
-            self.position.select(None);
-            return;
-        }
         let current = self.position.selected().unwrap_or(0);
-        if current == 0 {
-            self.position.select(Some(0));
-            return;
+        match (0..current)
+            .rev()
+            .find(|&row| self.rows[row].task().is_some())
+        {
+            Some(row) => self.position.select(Some(row)),
+            None => self.snap_selection(),
         }
-        self.position.select(Some(current - 1));
     }
 
-    /// API interaction
-    fn sync_tasks(&mut self) -> Result<(), u16> {
-        let (new_tasks, sync_token) = loop {
-            match self.client.get_tasks(&self.current_sync_token) {
-                Ok(result) => break result,
-                Err(500..=600) => continue,
-                Err(error_code) => return Err(error_code),
-            }
-        };
-        if self.current_sync_token == "*" {
-            self.tasks = new_tasks
-        } else {
-            self.tasks.extend(new_tasks);
+    fn snap_selection(&mut self) {
+        //! Moves the selection onto the nearest task row, looking down first then up
+        let current = self
+            .position
+            .selected()
+            .unwrap_or(0)
+            .min(self.rows.len().saturating_sub(1));
+        let row = (current..self.rows.len())
+            .chain((0..current).rev())
+            .find(|&row| self.rows[row].task().is_some());
+        self.position.select(row);
+    }
+
+    fn update_tasks(&mut self, change: impl FnOnce(&mut Vec<api::Task>)) {
+        //! Applies a change to the task list, then re-sorts it and updates the
+        //! listed tasks, keeping the same task selected if it still exists
+        let selected_id = self.selected_task().map(|index| self.tasks[index].get_id());
+        change(&mut self.tasks);
+        api::sort_tasks(&mut self.tasks);
+        if self.sort_by_deadline {
+            api::sort_tasks_by_deadline(&mut self.tasks);
+        }
+        self.refresh_view(selected_id);
+    }
+
+    fn refresh_view(&mut self, selected_id: Option<String>) {
+        //! Rebuilds the task list rows, selecting the task with selected_id
+        //! if it is listed, otherwise keeping the selection near where it was
+        self.rows = self.build_rows();
+        let selected_row = selected_id.and_then(|id| {
+            self.rows
+                .iter()
+                .position(|row| row.task().is_some_and(|index| self.tasks[index].id() == id))
+        });
+        match selected_row {
+            Some(row) => self.position.select(Some(row)),
+            None => self.snap_selection(),
         }
-        self.current_sync_token = sync_token;
-        Ok(())
     }
 
-    fn complete_current_task(&mut self) -> Result<(), u16> {
-        let current_index = match self.position.selected() {
+    /// API interaction, results are applied in handle_responses
+    fn sync_tasks(&mut self) {
+        self.worker
+            .send(worker::Request::Sync(self.current_sync_token.clone()));
+    }
+
+    fn resync_tasks(&mut self) {
+        //! Full sync replacing everything held locally, which puts back anything
+        //! changed locally that the server rejected, as a delta sync leaves out
+        //! whatever the server hasn't changed. The sync token is dropped straight
+        //! away so a cache saved before the sync finishes starts over too
+        self.current_sync_token = String::from("*");
+        self.sync_tasks();
+    }
+
+    fn complete_current_task(&mut self) {
+        let current_index = match self.selected_task() {
             Some(index) => index,
-            None => return Ok(()),
+            None => return,
         };
-        self.current_sync_token = loop {
-            match self.client.complete_task(&self.tasks[current_index]) {
-                Ok(result) => break result,
-                Err(500..=600) => continue,
-                Err(error_code) => return Err(error_code),
-            }
-        };
-        self.tasks.remove(current_index);
-        self.decrement_selection();
-        Ok(())
+        let task = self.tasks[current_index].clone();
+        // ask what should happen to the sub-tasks first, recurring tasks
+        // move to their next date and keep them
+        match self.has_children(task.id()) && !task.is_recurring() {
+            true => self.mode = Mode::Confirm(Box::new(tree::Confirm::CompleteChildren(task))),
+            false => self.complete_task(&task),
+        }
     }
 
-    fn add_task(&mut self) -> Result<(), u16> {
-        let new_task = loop {
-            match self.client.quick_add(self.inputs[0].clone()) {
-                Ok(result) => break result,
-                Err(500..=600) => continue,
-                Err(error_code) => return Err(error_code),
-            }
+    fn complete_task(&mut self, task: &api::Task) {
+        let undo = self.close_task(task);
+        self.record_undo(undo);
+    }
+
+    fn close_task(&mut self, task: &api::Task) -> undo::Undo {
+        //! Completes the task and its sub-tasks locally and queues the item_close.
+        //! Recurring tasks move to their next date instead, which the server works out.
+        //! Returns how to undo it
+        let mut completed = vec![task.get_id()];
+        if !task.is_recurring() {
+            completed.extend(self.descendants(task.id()));
+        }
+        let snapshots = completed
+            .iter()
+            .filter_map(|id| self.tasks.iter().find(|x| x.id() == id).cloned())
+            .collect();
+        if !task.is_recurring() {
+            self.update_tasks(|tasks| tasks.retain(|x| !completed.contains(&x.get_id())));
+        }
+        let command = api::Command::ItemClose { id: task.get_id() };
+        self.queue(vec![api::SyncCommand::new(command)]);
+        undo::Undo::completed(snapshots)
+    }
+
+    fn toast(&mut self, message: String) {
+        self.toast = Some((message, Instant::now()));
+    }
+
+    fn add_task(&mut self) {
+        //! Quick adds the typed task, so shorthand like "tomorrow p1 @shop #Work" is
+        //! parsed by the server. While offline it is queued to be added as it is instead
+        let text = std::mem::take(&mut self.inputs[0]);
+        match self.offline {
+            true => self.queue_add(text),
+            false => self.worker.send(worker::Request::QuickAdd(
+                uuid::Uuid::new_v4().to_string(),
+                text,
+            )),
+        }
+    }
+
+    fn inbox_id(&self) -> Option<String> {
+        self.projects
+            .iter()
+            .find(|project| project.is_inbox)
+            .map(|project| project.id.clone())
+    }
+
+    fn queue_add(&mut self, content: String) {
+        //! Adds the task to the project being viewed, or the inbox, under a temp id
+        //! until the server has it. The text is used as it is, without parsing shorthand
+        let temp_id = uuid::Uuid::new_v4().to_string();
+        let project_id = self
+            .project_filter
+            .clone()
+            .or_else(|| self.inbox_id())
+            .unwrap_or_default();
+        let task = api::Task::pending(temp_id.clone(), content, project_id);
+        let command = api::SyncCommand::new(task.add_command());
+        self.update_tasks(|tasks| tasks.push(task));
+        self.queue(vec![command.with_temp_id(temp_id)]);
+    }
+
+    fn quick_added(
+        &mut self,
+        request_id: String,
+        text: String,
+        result: Result<api::Task, ApiError>,
+    ) -> Result<(), Error> {
+        //! Lists the added task, moving it out of the inbox to the project being viewed
+        //! so it stays listed. A failed add isn't queued, as the server may have added it
+        //! before the connection dropped, retrying with the same request id is safe though
+        let retry = worker::Request::QuickAdd(request_id, text);
+        let Some(mut task) = self.check_result(result, retry)? else {
+            return Ok(());
         };
-        self.tasks.push(new_task);
-        self.inputs[0] = String::new();
-        self.current_sync_token = String::from("*");
+        let inbox_id = self.inbox_id();
+        let destination = self
+            .project_filter
+            .clone()
+            .filter(|id| inbox_id.as_deref() == Some(task.project_id()) && id != task.project_id());
+        let id = task.get_id();
+        if let Some(project_id) = &destination {
+            task.set_place(project_id.clone(), None);
+        }
+        self.update_tasks(|tasks| tasks.push(task));
+        match destination {
+            Some(project_id) => self.queue(vec![api::SyncCommand::new(api::Command::ItemMove {
+                id,
+                parent_id: None,
+                section_id: None,
+                project_id: Some(project_id),
+            })]),
+            None => self.persist(),
+        }
         Ok(())
     }
 
-    fn edit_task(&mut self) -> Result<(), u16> {
-        // get all data
-        let id = self.tasks[self.position.selected().unwrap()].get_id();
-        let content = self.inputs[0].clone();
-        self.inputs[0] = String::new();
-        let description = self.inputs[1].clone();
-        self.inputs[1] = String::new();
-        let labels = self.inputs[2]
-            .clone()
-            .split(',')
-            .map(String::from)
-            .collect();
-        self.inputs[2] = String::new();
-        let date = self.inputs[3].clone();
-        self.inputs[3] = String::new();
-        let priority = match self.inputs[4].clone().parse() {
-            Ok(x) => x,
-            Err(_) => return Ok(()),
-        }; //TODO
-        self.inputs[0] = String::new();
-
-        // create task object
-        let task = api::Task::create_task_obj(id, content, description, date, labels, priority);
-
-        // modify task api request
-        match self.client.edit(task.clone()) {
-            Ok(sync) => {
-                self.current_sync_token = sync;
-                self.tasks[self.position.selected().unwrap()] = task.clone();
+    fn edit_task(&mut self) -> Result<(), String> {
+        //! Sends the edit mode inputs as an update to the selected task,
+        //! or returns why the inputs are invalid
+        let priority = match self.inputs[4].trim().parse() {
+            Ok(x @ 1..=4) => x,
+            _ => {
+                return Err(format!(
+                    "priority must be a number from 1 to 4, not \"{}\"",
+                    self.inputs[4]
+                ))
             }
-            // Err(2) => {}
-            Err(x) => return Err(x),
         };
+        let index = match self.selected_task() {
+            Some(index) => index,
+            None => return Err(String::from("no task selected to edit")),
+        };
+        let duration = api::TaskDuration::parse(&self.inputs[5])?;
+        let deadline = api::Deadline::parse(&self.inputs[6])?;
+
+        // update the task locally and queue the change for the server
+        let mut task = self.tasks[index].edited(api::TaskEdit {
+            content: self.inputs[0].clone(),
+            description: self.inputs[1].clone(),
+            due_string: self.inputs[3].clone(),
+            labels: labels::split_labels(&self.inputs[2]),
+            priority,
+            duration,
+            deadline,
+        });
+        let old = self.tasks[index].clone();
+        let mut commands = vec![api::SyncCommand::new(task.update_command())];
+        let mut undo_commands = vec![api::SyncCommand::new(old.restore_command())];
+        let parent = self.edit_parent.take();
+        if let Some(parent) = parent.filter(|x| x.as_deref() != task.parent_id()) {
+            commands.push(api::SyncCommand::new(tree::move_command(
+                &task,
+                parent.as_deref(),
+            )));
+            undo_commands.push(api::SyncCommand::new(tree::move_command(
+                &task,
+                old.parent_id(),
+            )));
+            task.set_parent_id(parent);
+        }
+        self.update_tasks(|tasks| {
+            if let Some(old) = tasks.iter_mut().find(|x| x.id() == task.id()) {
+                *old = task;
+            }
+        });
+        self.queue(commands);
+        self.record_undo(undo::Undo {
+            action: "task edited",
+            tasks: vec![old],
+            commands: undo_commands,
+        });
         Ok(())
     }
 }
@@ -0,0 +1,114 @@
+use std::time::{Duration, Instant};
+
+use super::{
+    api::{ApiError, BatchResult, SyncCommand},
+    worker, App, Error, Notice,
+};
+
+/// How long to wait while offline before checking if the server can be reached again
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Most commands the sync endpoint accepts in one request
+const MAX_COMMANDS: usize = 100;
+
+impl App {
+    pub(super) fn queue(&mut self, commands: Vec<SyncCommand>) {
+        //! Adds task changes to the outbox, they should already be applied to the
+        //! local state. Saved straight away so they survive the app closing offline
+        self.outbox.extend(commands);
+        self.persist();
+        self.replay_outbox();
+    }
+
+    pub(super) fn replay_outbox(&mut self) {
+        //! Sends the outbox, unless some of it is already on its way. At most
+        //! MAX_COMMANDS go at a time, the rest follow once they have been applied
+        if self.replaying || self.outbox.is_empty() {
+            return;
+        }
+        self.replaying = true;
+        let commands = self.outbox.iter().take(MAX_COMMANDS).cloned().collect();
+        self.worker.send(worker::Request::Replay(commands));
+    }
+
+    pub(super) fn discard_outbox(&mut self) {
+        //! Drops every queued change, for when replaying them keeps failing. They were
+        //! already made locally, so everything is fully synced again to put them back
+        let count = self.outbox.len();
+        self.outbox.clear();
+        // undoing could refer to tasks that will now never be added
+        self.undo_stack.clear();
+        self.notice = None;
+        self.toast(format!("dropped {count} queued change(s)"));
+        self.resync_tasks();
+        self.persist();
+    }
+
+    pub(super) fn replayed(
+        &mut self,
+        sent: Vec<SyncCommand>,
+        result: Result<BatchResult, ApiError>,
+    ) -> Result<(), Error> {
+        //! Drops sent commands from the outbox, reporting any the server rejected
+        self.replaying = false;
+        let retry = worker::Request::Replay(sent.clone());
+        let Some(batch) = self.check_result(result, retry)? else {
+            if let (true, Some(notice)) = (self.offline, &mut self.notice) {
+                notice.message = format!(
+                    "offline, {} change(s) will be sent once the server can be reached",
+                    self.outbox.len()
+                );
+            }
+            return Ok(());
+        };
+
+        // applied or rejected, either way there is no point sending them again
+        self.outbox
+            .retain(|command| !sent.iter().any(|x| x.uuid() == command.uuid()));
+        for command in &mut self.outbox {
+            command.remap(&batch.temp_id_mapping);
+        }
+        self.update_tasks(|tasks| {
+            for task in tasks {
+                task.remap(&batch.temp_id_mapping);
+            }
+        });
+        for undo in &mut self.undo_stack {
+            undo.tasks
+                .iter_mut()
+                .for_each(|task| task.remap(&batch.temp_id_mapping));
+            undo.commands
+                .iter_mut()
+                .for_each(|command| command.remap(&batch.temp_id_mapping));
+        }
+        let conflicts: Vec<String> = batch
+            .failures()
+            .iter()
+            .map(|(command, error)| format!("{} rejected: {error}", command.name()))
+            .collect();
+        self.current_sync_token = batch.sync_token;
+        if conflicts.is_empty() {
+            // fetches what the server made of the changes, e.g. parsed due dates
+            self.sync_tasks();
+        } else {
+            self.notice = Some(Notice {
+                message: format!("changes not applied, {}", conflicts.join(", ")),
+                retry: None,
+            });
+            // rejected changes were already made locally, only a full sync undoes them
+            self.resync_tasks();
+        }
+        self.persist();
+        self.replay_outbox();
+        Ok(())
+    }
+
+    pub(super) fn poll_reconnect(&mut self) {
+        //! While offline, syncs every so often to find out when the server is back
+        if !self.offline || self.worker.is_busy() {
+            self.reconnect_at = Instant::now() + RECONNECT_INTERVAL;
+        } else if Instant::now() >= self.reconnect_at {
+            self.sync_tasks();
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use crossterm::event::KeyCode;
+
+use super::{api::Resource, App, Mode};
+
+/// A row of the project sidebar
+pub struct ProjectRow {
+    /// index into App.projects, None for the "all tasks" row
+    pub project: Option<usize>,
+    pub depth: usize,
+    pub has_children: bool,
+}
+
+impl App {
+    pub(super) fn project_rows(&self) -> Vec<ProjectRow> {
+        //! Project tree in sidebar order, inbox first then by child_order,
+        //! skipping sub-projects of collapsed projects
+        self.project_tree(true)
+    }
+
+    pub(super) fn project_order(&self) -> Vec<usize> {
+        //! Indices into App.projects of every project, in sidebar order
+        self.project_tree(false)
+            .iter()
+            .filter_map(|row| row.project)
+            .collect()
+    }
+
+    fn project_tree(&self, skip_collapsed: bool) -> Vec<ProjectRow> {
+        let mut children: HashMap<Option<&str>, Vec<usize>> = HashMap::new();
+        for (index, project) in self.projects.iter().enumerate() {
+            // projects whose parent isn't synced are shown at the top level
+            let parent = project
+                .parent_id
+                .as_deref()
+                .filter(|id| self.projects.iter().any(|x| x.id() == *id));
+            children.entry(parent).or_default().push(index);
+        }
+        for siblings in children.values_mut() {
+            siblings.sort_by_key(|&index| {
+                let project = &self.projects[index];
+                (!project.is_inbox, project.child_order)
+            });
+        }
+
+        let mut rows = vec![ProjectRow {
+            project: None,
+            depth: 0,
+            has_children: false,
+        }];
+        // depth first walk, stack holds (project index, depth) in reverse order
+        let mut stack: Vec<(usize, usize)> = children
+            .get(&None)
+            .map(|roots| roots.iter().rev().map(|&index| (index, 0)).collect())
+            .unwrap_or_default();
+        while let Some((index, depth)) = stack.pop() {
+            let id = self.projects[index].id();
+            let sub_projects = children.get(&Some(id));
+            rows.push(ProjectRow {
+                project: Some(index),
+                depth,
+                has_children: sub_projects.is_some(),
+            });
+            if let Some(sub_projects) = sub_projects {
+                if !(skip_collapsed && self.collapsed_projects.contains(id)) {
+                    stack.extend(sub_projects.iter().rev().map(|&x| (x, depth + 1)));
+                }
+            }
+        }
+        rows
+    }
+
+    pub(super) fn project_rows_text(&self) -> Vec<(String, String)> {
+        //! Sidebar rows as (text, todoist colour name) pairs
+        self.project_rows()
+            .iter()
+            .map(|row| match row.project {
+                None => (String::from("all tasks"), String::new()),
+                Some(index) => {
+                    let project = &self.projects[index];
+                    let marker = match (
+                        row.has_children,
+                        self.collapsed_projects.contains(project.id()),
+                    ) {
+                        (false, _) => " ",
+                        (true, true) => "▸",
+                        (true, false) => "▾",
+                    };
+                    (
+                        format!("{}{marker} {}", "  ".repeat(row.depth), project.name),
+                        project.color.clone(),
+                    )
+                }
+            })
+            .collect()
+    }
+
+    pub(super) fn project_name(&self, id: &str) -> &str {
+        self.projects
+            .iter()
+            .find(|project| project.id() == id)
+            .map(|project| project.name.as_str())
+            .unwrap_or("unknown project")
+    }
+
+    pub(super) fn handle_projects_key(&mut self, key: KeyCode) {
+        //! Key handling while the project sidebar has focus
+        let rows = self.project_rows();
+        let current = self
+            .project_position
+            .selected()
+            .unwrap_or(0)
+            .min(rows.len() - 1);
+        let project = rows[current]
+            .project
+            .map(|index| self.projects[index].id.clone());
+        match key {
+            KeyCode::Char('j') | KeyCode::Down if current + 1 < rows.len() => {
+                self.project_position.select(Some(current + 1))
+            }
+            KeyCode::Char('k') | KeyCode::Up => self
+                .project_position
+                .select(Some(current.saturating_sub(1))),
+
+            // filter the task list to the selected project
+            KeyCode::Enter => {
+                self.project_filter = project;
+                self.refresh_view(None);
+                self.mode = Mode::Normal;
+            }
+
+            // fold and unfold sub-projects
+            KeyCode::Char(' ') | KeyCode::Tab if rows[current].has_children => {
+                if let Some(id) = project {
+                    if !self.collapsed_projects.remove(&id) {
+                        self.collapsed_projects.insert(id);
+                    }
+                }
+            }
+            KeyCode::Char('h') | KeyCode::Left => {
+                if let Some(id) = project.filter(|_| rows[current].has_children) {
+                    self.collapsed_projects.insert(id);
+                }
+            }
+            KeyCode::Char('l') | KeyCode::Right => {
+                if let Some(id) = project {
+                    self.collapsed_projects.remove(&id);
+                }
+            }
+
+            // add, edit, archive or delete projects
+            KeyCode::Char('a' | 'r' | 'A' | 'D') => self.project_command(key, project),
+
+            KeyCode::Esc | KeyCode::Backspace | KeyCode::Char('p') => self.mode = Mode::Normal,
+            _ => {}
+        }
+    }
+}
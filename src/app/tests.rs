@@ -0,0 +1,520 @@
+//! End-to-end tests running the app against the in-memory stand-in server
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde_json::json;
+
+use super::mock::{MockServer, Response, Todoist};
+use super::*;
+
+/// An app pointed at a stand-in server, with its cache kept in a temporary directory
+struct Harness {
+    app: App,
+    todoist: Arc<Mutex<Todoist>>,
+    server: MockServer,
+    dir: PathBuf,
+}
+
+impl Harness {
+    fn start(todoist: Todoist) -> Harness {
+        let (server, todoist) = todoist.start();
+        let config = Config {
+            api_url: Some(server.url.clone()),
+            retry: RetryPolicy {
+                max_attempts: 1,
+                ..RetryPolicy::default()
+            },
+            ..Config::default()
+        };
+        let dir = std::env::temp_dir().join(format!("todoist-rs-test-{}", uuid::Uuid::new_v4()));
+        let mut app = App::new(String::from("token"), config);
+        app.cache_path = Some(dir.join("cache.json"));
+        Harness {
+            app,
+            todoist,
+            server,
+            dir,
+        }
+    }
+
+    fn synced(todoist: Todoist) -> Harness {
+        //! Started and fully synced
+        let mut harness = Harness::start(todoist);
+        harness.app.sync_tasks();
+        harness.settle();
+        harness
+    }
+
+    fn settle(&mut self) {
+        //! Handles responses until nothing is left in flight
+        let started = Instant::now();
+        loop {
+            self.app.handle_responses().unwrap();
+            if !self.app.worker.is_busy() {
+                return;
+            }
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "requests never finished"
+            );
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn task(&self, content: &str) -> Option<&api::Task> {
+        self.app.tasks.iter().find(|task| task.content() == content)
+    }
+
+    fn select(&mut self, content: &str) {
+        let id = self.task(content).unwrap().get_id();
+        let row = self.app.rows.iter().position(|row| {
+            row.task()
+                .is_some_and(|index| self.app.tasks[index].id() == id)
+        });
+        self.app.position.select(row);
+    }
+
+    fn server_item(&self, id: &str) -> serde_json::Value {
+        self.todoist.lock().unwrap().item(id).unwrap().clone()
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[test]
+fn sync_fetches_tasks_then_only_changes() {
+    let mut todoist = Todoist::new();
+    todoist.add(json!({"content": "buy milk", "priority": 4}));
+    todoist.add(json!({"content": "walk dog"}));
+    let mut harness = Harness::synced(todoist);
+    assert_eq!(harness.app.tasks.len(), 2);
+    assert_eq!(harness.app.projects[0].name, "Inbox");
+    assert!(harness.app.notice.is_none());
+
+    let id = harness
+        .todoist
+        .lock()
+        .unwrap()
+        .add(json!({"content": "water plants"}));
+    let requests = harness.server.requests().len();
+    harness.app.sync_tasks();
+    harness.settle();
+    assert!(harness.task("water plants").is_some());
+    assert_eq!(harness.app.tasks.len(), 3);
+    // the second sync asked for changes since the first
+    let request = &harness.server.requests()[requests];
+    assert_ne!(request.field("sync_token").as_deref(), Some("*"));
+    assert_eq!(harness.server_item(&id)["content"], "water plants");
+}
+
+#[test]
+fn completing_removes_the_task_straight_away() {
+    let mut todoist = Todoist::new();
+    let id = todoist.add(json!({"content": "buy milk"}));
+    let mut harness = Harness::synced(todoist);
+
+    let task = harness.task("buy milk").unwrap().clone();
+    harness.app.complete_task(&task);
+    assert!(harness.task("buy milk").is_none());
+    harness.settle();
+    assert_eq!(harness.server_item(&id)["checked"], true);
+    assert!(harness.app.outbox.is_empty());
+    assert!(harness.task("buy milk").is_none());
+}
+
+#[test]
+fn quick_add_parses_shorthand() {
+    let mut harness = Harness::synced(Todoist::new());
+    harness.app.inputs[0] = String::from("buy milk p1 @shop #Work");
+    harness.app.add_task();
+    harness.settle();
+
+    let task = harness.task("buy milk").unwrap();
+    assert_eq!(task.project_id(), "work");
+    assert_eq!(task.labels(), ["shop"]);
+    let item = harness.server_item(&task.get_id());
+    assert_eq!(item["priority"], 4);
+    assert!(harness.app.outbox.is_empty());
+}
+
+#[test]
+fn quick_add_keeps_the_task_in_the_viewed_project() {
+    let mut harness = Harness::synced(Todoist::new());
+    harness.app.project_filter = Some(String::from("work"));
+    harness.app.inputs[0] = String::from("buy milk");
+    harness.app.add_task();
+    harness.settle();
+
+    let task = harness.task("buy milk").unwrap();
+    assert_eq!(task.project_id(), "work");
+    assert_eq!(harness.server_item(&task.get_id())["project_id"], "work");
+}
+
+#[test]
+fn adding_offline_is_queued_until_the_server_is_back() {
+    let mut harness = Harness::synced(Todoist::new());
+    harness.todoist.lock().unwrap().offline = true;
+    harness.app.sync_tasks();
+    harness.settle();
+    harness.app.project_filter = Some(String::from("work"));
+    harness.app.inputs[0] = String::from("buy milk p1");
+    harness.app.add_task();
+    harness.settle();
+
+    // kept as typed, in the project being viewed, under a temp id
+    assert!(harness.app.offline);
+    assert_eq!(harness.app.outbox.len(), 1);
+    let temp_id = harness.task("buy milk p1").unwrap().get_id();
+    assert_eq!(harness.task("buy milk p1").unwrap().project_id(), "work");
+
+    harness.todoist.lock().unwrap().offline = false;
+    harness.app.sync_tasks();
+    harness.settle();
+    assert!(!harness.app.offline);
+    assert!(harness.app.outbox.is_empty());
+    let id = harness.task("buy milk p1").unwrap().get_id();
+    assert_ne!(id, temp_id);
+    let item = harness.server_item(&id);
+    assert_eq!(item["content"], "buy milk p1");
+    assert_eq!(item["project_id"], "work");
+    assert_eq!(harness.app.tasks.len(), 1);
+}
+
+#[test]
+fn quick_adds_that_lose_their_response_are_retried_not_queued() {
+    let mut harness = Harness::synced(Todoist::new());
+    harness.todoist.lock().unwrap().lost_responses = 1;
+    harness.app.inputs[0] = String::from("buy milk p1");
+    harness.app.add_task();
+    harness.settle();
+
+    // the server added it, but without hearing back it can only be retried
+    assert!(harness.app.outbox.is_empty());
+    assert!(harness.task("buy milk").is_none());
+    assert!(harness
+        .app
+        .notice
+        .as_ref()
+        .is_some_and(|notice| notice.retry.is_some()));
+    harness
+        .app
+        .handle_key_event(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::NONE));
+    harness.settle();
+    harness.app.sync_tasks();
+    harness.settle();
+    assert_eq!(harness.app.tasks.len(), 1);
+    assert!(harness.task("buy milk").is_some());
+    assert!(harness.todoist.lock().unwrap().item("2").is_none());
+}
+
+#[test]
+fn editing_updates_the_server() {
+    let mut todoist = Todoist::new();
+    let id = todoist.add(json!({"content": "buy milk", "labels": ["shop"]}));
+    let mut harness = Harness::synced(todoist);
+    harness.select("buy milk");
+    harness.app.inputs = [
+        "buy oat milk",
+        "the barista one",
+        "shop, errand",
+        "2024-05-02",
+        "4",
+        "15m",
+        "2024-05-03",
+    ]
+    .map(String::from)
+    .to_vec();
+    harness.app.edit_task().unwrap();
+    assert!(harness.task("buy oat milk").is_some());
+    harness.settle();
+
+    let item = harness.server_item(&id);
+    assert_eq!(item["content"], "buy oat milk");
+    assert_eq!(item["description"], "the barista one");
+    assert_eq!(item["labels"], json!(["shop", "errand"]));
+    assert_eq!(item["priority"], 4);
+    assert_eq!(item["due"]["date"], "2024-05-02");
+    assert_eq!(item["duration"], json!({"amount": 15, "unit": "minute"}));
+    assert_eq!(item["deadline"], json!({"date": "2024-05-03"}));
+    // the synced due date replaces the string waiting to be parsed
+    let task = harness.task("buy oat milk").unwrap();
+    assert!(task
+        .to_info_string(&harness.app.due_format)
+        .contains("2024-05-02"));
+    assert!(!task
+        .to_info_string(&harness.app.due_format)
+        .contains("waiting to sync"));
+}
+
+#[test]
+fn cache_is_only_loaded_for_the_account_that_saved_it() {
+    let mut todoist = Todoist::new();
+    todoist.add(json!({"content": "buy milk"}));
+    let harness = Harness::synced(todoist);
+    harness.app.save_cache().unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let path = harness.app.cache_path.as_ref().unwrap();
+        let mode = std::fs::metadata(path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    let loaded = |token: &str| {
+        let config = Config {
+            api_url: Some(harness.app.api_url.clone()),
+            ..Config::default()
+        };
+        let mut app = App::new(token.to_string(), config);
+        app.cache_path = harness.app.cache_path.clone();
+        app.load_cache();
+        app.tasks.len()
+    };
+    assert_eq!(loaded("token"), 1);
+    assert_eq!(loaded("another account"), 0);
+}
+
+#[test]
+fn rejected_changes_are_put_back() {
+    let mut todoist = Todoist::new();
+    let id = todoist.add(json!({"content": "buy milk"}));
+    todoist.rejects.push("item_close");
+    let mut harness = Harness::synced(todoist);
+
+    let task = harness.task("buy milk").unwrap().clone();
+    harness.app.complete_task(&task);
+    assert!(harness.task("buy milk").is_none());
+    harness.settle();
+    assert!(harness.task("buy milk").is_some());
+    assert_eq!(harness.server_item(&id)["checked"], false);
+    let notice = harness.app.notice.as_ref().unwrap();
+    assert!(notice.message.contains("item_close rejected"));
+    assert!(harness.app.outbox.is_empty());
+}
+
+#[test]
+fn ctrl_u_syncs_everything_again() {
+    let mut harness = Harness::synced(Todoist::new());
+    let requests = harness.server.requests().len();
+    harness
+        .app
+        .handle_key_event(KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL));
+    harness.settle();
+    let requests = &harness.server.requests()[requests..];
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].field("sync_token").as_deref(), Some("*"));
+}
+
+#[test]
+fn retrying_a_failed_replay_sends_the_outbox_once() {
+    let mut todoist = Todoist::new();
+    let id = todoist.add(json!({"content": "buy milk"}));
+    let mut harness = Harness::synced(todoist);
+    harness.todoist.lock().unwrap().offline = true;
+    let task = harness.task("buy milk").unwrap().clone();
+    harness.app.complete_task(&task);
+    harness.settle();
+    assert!(harness
+        .app
+        .notice
+        .as_ref()
+        .is_some_and(|notice| notice.retry.is_some()));
+
+    // the reconnect check is already on its way when retry is pressed
+    harness.todoist.lock().unwrap().offline = false;
+    let requests = harness.server.requests().len();
+    harness.app.sync_tasks();
+    harness
+        .app
+        .handle_key_event(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::NONE));
+    harness.settle();
+    let replays = harness.server.requests()[requests..]
+        .iter()
+        .filter(|request| request.field("commands").is_some())
+        .count();
+    assert_eq!(replays, 1);
+    assert!(harness.app.outbox.is_empty());
+    assert_eq!(harness.server_item(&id)["checked"], true);
+}
+
+#[test]
+fn undoing_a_completion_puts_kept_sub_tasks_back() {
+    let mut todoist = Todoist::new();
+    let parent = todoist.add(json!({"content": "shopping"}));
+    let child = todoist.add(json!({"content": "buy milk", "parent_id": parent}));
+    let mut harness = Harness::synced(todoist);
+
+    // complete the parent but keep its sub-task
+    let task = harness.task("shopping").unwrap().clone();
+    harness.app.mode = Mode::Confirm(Box::new(tree::Confirm::CompleteChildren(task)));
+    harness.app.handle_confirm_key(KeyCode::Char('n'));
+    harness.settle();
+    assert_eq!(harness.server_item(&parent)["checked"], true);
+    assert_eq!(
+        harness.server_item(&child)["parent_id"],
+        serde_json::Value::Null
+    );
+    assert_eq!(harness.task("buy milk").unwrap().parent_id(), None);
+
+    harness.app.undo();
+    assert_eq!(
+        harness.task("buy milk").unwrap().parent_id(),
+        Some(parent.as_str())
+    );
+    harness.settle();
+    assert!(harness.task("shopping").is_some());
+    assert_eq!(harness.server_item(&parent)["checked"], false);
+    assert_eq!(harness.server_item(&child)["parent_id"], parent.as_str());
+    assert_eq!(
+        harness.task("buy milk").unwrap().parent_id(),
+        Some(parent.as_str())
+    );
+}
+
+#[test]
+fn forbidden_requests_are_reported_not_fatal() {
+    let server = MockServer::scripted(vec![Response::status(401), Response::status(403)]);
+    let config = Config {
+        api_url: Some(server.url.clone()),
+        retry: RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        },
+        ..Config::default()
+    };
+    let mut app = App::new(String::from("token"), config);
+    let handled = |app: &mut App| {
+        app.sync_tasks();
+        let started = Instant::now();
+        loop {
+            let result = app.handle_responses();
+            if result.is_err() || !app.worker.is_busy() {
+                return result;
+            }
+            assert!(started.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(5));
+        }
+    };
+    // a bad token ends the app
+    assert!(handled(&mut app).is_err());
+    // but a request that isn't allowed only leaves a notice, with nothing to retry
+    assert!(handled(&mut app).is_ok());
+    let notice = app.notice.as_ref().unwrap();
+    assert!(notice.message.contains("403"));
+    assert!(notice.retry.is_none());
+}
+
+#[test]
+fn space_unfolds_the_heading_next_to_the_selected_task() {
+    let mut todoist = Todoist::new();
+    todoist.add(json!({"content": "buy milk"}));
+    todoist.add(json!({"content": "write report", "project_id": "work"}));
+    let mut harness = Harness::synced(todoist);
+    let space = KeyEvent::new(KeyCode::Char(' '), KeyModifiers::NONE);
+
+    harness.select("buy milk");
+    harness.app.handle_key_event(space);
+    assert!(harness.app.folded.contains("project/inbox"));
+    assert_eq!(harness.app.rows.len(), 3);
+    let selected = harness.app.selected_task().unwrap();
+    assert_eq!(harness.app.tasks[selected].content(), "write report");
+
+    // the folded inbox heading is just above, past the work heading
+    harness.app.handle_key_event(space);
+    assert!(harness.app.folded.is_empty());
+    assert_eq!(harness.app.rows.len(), 4);
+    let selected = harness.app.selected_task().unwrap();
+    assert_eq!(harness.app.tasks[selected].content(), "write report");
+}
+
+#[test]
+fn long_outboxes_are_replayed_a_hundred_at_a_time() {
+    let mut todoist = Todoist::new();
+    let ids: Vec<String> = (0..150)
+        .map(|number| todoist.add(json!({"content": format!("task {number}")})))
+        .collect();
+    let mut harness = Harness::synced(todoist);
+    harness.todoist.lock().unwrap().offline = true;
+    harness.app.sync_tasks();
+    harness.settle();
+    for task in harness.app.tasks.clone() {
+        harness.app.complete_task(&task);
+    }
+    harness.settle();
+    assert_eq!(harness.app.outbox.len(), 150);
+
+    harness.todoist.lock().unwrap().offline = false;
+    let requests = harness.server.requests().len();
+    harness.app.replay_outbox();
+    harness.settle();
+    assert!(harness.app.outbox.is_empty());
+    let sizes: Vec<usize> = harness.server.requests()[requests..]
+        .iter()
+        .filter_map(|request| request.field("commands"))
+        .map(|commands| {
+            serde_json::from_str::<Vec<serde_json::Value>>(&commands)
+                .unwrap()
+                .len()
+        })
+        .collect();
+    assert_eq!(sizes, [100, 50]);
+    assert!(ids
+        .iter()
+        .all(|id| harness.server_item(id)["checked"] == true));
+}
+
+#[test]
+fn a_failing_replay_can_be_dropped() {
+    let mut todoist = Todoist::new();
+    let id = todoist.add(json!({"content": "buy milk"}));
+    todoist.failing_writes = Some(400);
+    let mut harness = Harness::synced(todoist);
+    let task = harness.task("buy milk").unwrap().clone();
+    harness.app.complete_task(&task);
+    harness.settle();
+    assert_eq!(harness.app.outbox.len(), 1);
+    assert!(harness.task("buy milk").is_none());
+
+    harness
+        .app
+        .handle_key_event(KeyEvent::new(KeyCode::Char('X'), KeyModifiers::SHIFT));
+    assert!(harness.app.outbox.is_empty());
+    assert!(harness.app.notice.is_none());
+    assert!(harness.app.undo_stack.is_empty());
+    harness.settle();
+    // the full sync puts back how the server has it
+    assert!(harness.task("buy milk").is_some());
+    assert_eq!(harness.server_item(&id)["checked"], false);
+}
+
+#[test]
+fn undoing_a_delete_adds_the_tasks_again() {
+    let mut todoist = Todoist::new();
+    let parent = todoist.add(json!({"content": "shopping", "priority": 3}));
+    todoist.add(json!({"content": "buy milk", "parent_id": parent}));
+    let mut harness = Harness::synced(todoist);
+
+    harness.app.delete_task(&parent);
+    harness.settle();
+    assert!(harness.app.tasks.is_empty());
+    assert_eq!(harness.server_item(&parent)["is_deleted"], true);
+
+    harness.app.undo();
+    assert_eq!(harness.app.tasks.len(), 2);
+    harness.settle();
+    assert!(harness.app.outbox.is_empty());
+    let restored = harness.task("shopping").unwrap().get_id();
+    assert_ne!(restored, parent);
+    assert_eq!(harness.server_item(&restored)["priority"], 3);
+    let child = harness.task("buy milk").unwrap().get_id();
+    assert_eq!(harness.server_item(&child)["parent_id"], restored.as_str());
+    assert_eq!(
+        harness.task("buy milk").unwrap().parent_id(),
+        Some(restored.as_str())
+    );
+    assert_eq!(harness.app.tasks.len(), 2);
+}
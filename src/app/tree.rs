@@ -0,0 +1,181 @@
+use crossterm::event::KeyCode;
+
+use super::{
+    api::{self, Command, CommandBatch, Resource, SyncCommand},
+    worker, App, Mode,
+};
+
+/// Questions asked before acting, answered with y or n
+pub enum Confirm {
+    /// Completing a task with sub-tasks. Yes completes the sub-tasks too,
+    /// no keeps them by moving them up a level first
+    CompleteChildren(api::Task),
+    /// Deleting a task along with its sub-tasks, undoing adds them again as new tasks
+    DeleteTask(api::Task),
+    /// Sending a command that can't be taken back, going back to the mode after
+    Send {
+        question: String,
+        command: Command,
+        back: Mode,
+    },
+}
+
+impl Confirm {
+    pub fn question(&self) -> String {
+        match self {
+            Confirm::CompleteChildren(task) => {
+                format!("\"{}\" has sub-tasks, complete them too?", task.content())
+            }
+            Confirm::DeleteTask(task) => {
+                format!("delete \"{}\" and any sub-tasks?", task.content())
+            }
+            Confirm::Send { question, .. } => question.clone(),
+        }
+    }
+}
+
+impl App {
+    pub(super) fn has_children(&self, id: &str) -> bool {
+        self.tasks.iter().any(|task| task.parent_id() == Some(id))
+    }
+
+    pub(super) fn descendants(&self, id: &str) -> Vec<String> {
+        //! Ids of every sub-task below the task, at any depth
+        let mut found: Vec<String> = Vec::new();
+        let mut stack = vec![id.to_string()];
+        while let Some(parent) = stack.pop() {
+            for task in self.tasks.iter().filter(|x| x.parent_id() == Some(&parent)) {
+                found.push(task.get_id());
+                stack.push(task.get_id());
+            }
+        }
+        found
+    }
+
+    pub(super) fn fold_task(&mut self, fold: bool) {
+        //! Folds or unfolds the sub-tasks of the selected task. Folding a task without
+        //! sub-tasks folds its parent instead, moving the selection onto the parent
+        let Some(index) = self.selected_task() else {
+            return;
+        };
+        let task = &self.tasks[index];
+        let target = match (fold, self.has_children(task.id())) {
+            (true, false) => match task.parent_id() {
+                Some(parent_id) => parent_id.to_string(),
+                None => return,
+            },
+            _ => task.get_id(),
+        };
+        match fold {
+            true => self.folded.insert(format!("task/{target}")),
+            false => self.folded.remove(&format!("task/{target}")),
+        };
+        self.refresh_view(Some(target));
+    }
+
+    pub(super) fn handle_confirm_key(&mut self, key: KeyCode) {
+        //! Answers the question being asked, any key other than y or n cancels
+        let Mode::Confirm(confirm) = std::mem::replace(&mut self.mode, Mode::Normal) else {
+            return;
+        };
+        match (*confirm, key) {
+            (Confirm::CompleteChildren(task), KeyCode::Char('y')) => {
+                // closing a task closes its sub-tasks as well
+                self.complete_task(&task)
+            }
+            (Confirm::CompleteChildren(task), KeyCode::Char('n')) => {
+                // sub-tasks move up a level to keep them open, undoing
+                // puts them back under the task once it is reopened
+                let children: Vec<api::Task> = self
+                    .tasks
+                    .iter()
+                    .filter(|x| x.parent_id() == Some(task.id()))
+                    .cloned()
+                    .collect();
+                let commands = children
+                    .iter()
+                    .map(|child| SyncCommand::new(move_command(child, task.parent_id())))
+                    .collect();
+                self.update_tasks(|tasks| {
+                    for child in tasks
+                        .iter_mut()
+                        .filter(|x| x.parent_id() == Some(task.id()))
+                    {
+                        child.set_parent_id(task.parent_id().map(String::from));
+                    }
+                });
+                self.queue(commands);
+                let mut undo = self.close_task(&task);
+                undo.commands.extend(
+                    children
+                        .iter()
+                        .map(|child| SyncCommand::new(move_command(child, Some(task.id())))),
+                );
+                undo.tasks.extend(children);
+                self.record_undo(undo);
+            }
+            (Confirm::DeleteTask(task), KeyCode::Char('y')) => self.delete_task(task.id()),
+            (Confirm::Send { command, back, .. }, key) => {
+                if key == KeyCode::Char('y') {
+                    let batch = CommandBatch::new().with(command);
+                    self.worker.send(worker::Request::Batch(batch));
+                }
+                self.mode = back;
+            }
+            _ => {}
+        }
+    }
+
+    pub(super) fn indent_parent(&self, index: usize, indent: bool) -> Option<Option<String>> {
+        //! New parent for the task after indenting or outdenting it once from
+        //! where edit mode currently has it, None if it can't move that way.
+        //! Indenting makes it a sub-task of the sibling listed above it,
+        //! outdenting makes it a sibling of its parent
+        let task = &self.tasks[index];
+        let parent = match &self.edit_parent {
+            Some(pending) => pending.clone(),
+            None => task.parent_id().map(String::from),
+        };
+        if !indent {
+            let parent = parent?;
+            let grandparent = self
+                .tasks
+                .iter()
+                .find(|x| x.id() == parent)
+                .and_then(|x| x.parent_id().map(String::from));
+            return Some(grandparent);
+        }
+
+        let descendants = self.descendants(task.id());
+        let position = self.rows.iter().position(|row| row.task() == Some(index))?;
+        self.rows[..position]
+            .iter()
+            .rev()
+            .filter_map(|row| row.task())
+            .map(|other| &self.tasks[other])
+            .find(|other| {
+                other.id() != task.id()
+                    && other.parent_id() == parent.as_deref()
+                    && other.project_id() == task.project_id()
+                    && other.section_id() == task.section_id()
+                    && !descendants.iter().any(|id| id == other.id())
+            })
+            .map(|sibling| Some(sibling.get_id()))
+    }
+}
+
+pub fn move_command(task: &api::Task, parent_id: Option<&str>) -> Command {
+    //! item_move putting the task under the parent, or at the top level of
+    //! its section or project if there is no parent
+    let (section_id, project_id) = match (parent_id, task.section_id()) {
+        (Some(_), _) => (None, None),
+        (None, Some(section_id)) => (Some(section_id.to_string()), None),
+        (None, None) => (None, Some(task.project_id().to_string())),
+    };
+    Command::ItemMove {
+        id: task.get_id(),
+        parent_id: parent_id.map(String::from),
+        section_id,
+        project_id,
+    }
+}
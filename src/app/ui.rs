@@ -4,54 +4,66 @@ use ratatui::{
     widgets::{block::*, *},
 };
 
-pub fn render_normal_ui(frame: &mut Frame, tasks: &Vec<String>, position: &mut ListState) {
+/// A row of the task list
+pub struct ListEntry {
+    pub text: String,
+    /// headings are styled differently and never highlighted
+    pub header: bool,
+}
+
+pub fn render_normal_ui(
+    frame: &mut Frame,
+    area: Rect,
+    tasks: &[ListEntry],
+    position: &mut ListState,
+    title: &str,
+) {
     //! Using &mut Frame renders the main list as a stateful widget
-    frame.render_stateful_widget(list(tasks), frame.size(), position)
+    frame.render_stateful_widget(list(tasks, title), area, position)
 }
 
 pub fn render_create_ui(
     frame: &mut Frame,
-    tasks: &Vec<String>,
+    area: Rect,
+    tasks: &[ListEntry],
     position: &mut ListState,
+    title: &str,
     create_input: &str,
 ) {
     //! Using &mut Frame renders the main list as a stateful widget, and the input box widget.
     let layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints(vec![Constraint::Max(4), Constraint::Fill(1)])
-        .split(frame.size());
+        .split(area);
 
     frame.render_widget(
         input_box(create_input, String::from("Create Task")),
         layout[0],
     );
-    frame.render_stateful_widget(list(tasks), layout[1], position)
+    frame.render_stateful_widget(list(tasks, title), layout[1], position)
 }
 
 pub fn render_info_ui(
     frame: &mut Frame,
-    tasks: &Vec<String>,
+    area: Rect,
+    tasks: &[ListEntry],
     position: &mut ListState,
+    title: &str,
     taskinfo: String,
 ) {
     //! Using &mut Frame renders the main list as a stateful widget and the info panel widget.
     let layout = Layout::default()
         .direction(Direction::Horizontal)
         .constraints(vec![Constraint::Percentage(50), Constraint::Percentage(50)])
-        .split(frame.size());
+        .split(area);
 
-    frame.render_stateful_widget(list(tasks), layout[0], position);
+    frame.render_stateful_widget(list(tasks, title), layout[0], position);
     frame.render_widget(infomation_panel(&taskinfo), layout[1]);
 }
 
-pub fn render_edit_ui(
-    frame: &mut Frame,
-    title: &str,
-    description: &str,
-    labels: &str,
-    date: &str,
-    priority: &str,
-) {
+pub fn render_edit_ui(frame: &mut Frame, area: Rect, inputs: &[String], parent: &str) {
+    //! Renders the edit form, inputs are title, description, labels, date, priority, duration
+    //! and deadline
     let layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints(vec![
@@ -60,41 +72,431 @@ pub fn render_edit_ui(
             Constraint::Max(4),
             Constraint::Max(4),
             Constraint::Max(4),
+            Constraint::Max(3),
+            Constraint::Max(3),
+            Constraint::Max(3),
         ])
-        .split(frame.size());
+        .split(area);
 
+    let titles = [
+        " title ",
+        " description ",
+        " labels (comma seperated, ctrl+l to pick) ",
+        " due (e.g. tomorrow 9am, every monday) ",
+        " priority (1-4 inclusive) ",
+        " duration (e.g. 30m, 2h, 1d) ",
+        " deadline (YYYY-MM-DD) ",
+    ];
+    for (index, (input, title)) in inputs.iter().zip(titles).enumerate() {
+        frame.render_widget(
+            multiple_input_box(input, String::from(title)),
+            layout[index],
+        );
+    }
     frame.render_widget(
-        multiple_input_box(title, String::from(" title ")),
-        layout[0],
+        multiple_input_box(
+            parent,
+            String::from(" parent (ctrl+← outdent, ctrl+→ indent) "),
+        ),
+        layout[7],
     );
+}
+
+pub fn render_confirm(frame: &mut Frame, area: Rect, question: &str) {
+    //! Renders a yes or no question in a popup over the middle of the area
+    let popup = centered(area, question.chars().count() as u16 + 4, 4);
+    let text = vec![
+        Line::from(question.to_string()),
+        Line::from(vec![
+            " y ".magenta(),
+            "/".into(),
+            " n ".magenta(),
+            "─ any other key to cancel".into(),
+        ]),
+    ];
+    frame.render_widget(Clear, popup);
     frame.render_widget(
-        multiple_input_box(description, String::from(" description ")),
-        layout[1],
+        Paragraph::new(text).block(Block::default().borders(Borders::ALL)),
+        popup,
     );
-    frame.render_widget(
-        multiple_input_box(labels, String::from(" labels (comma seperated) ")),
-        layout[2],
+}
+
+pub fn render_manage(
+    frame: &mut Frame,
+    area: Rect,
+    title: &str,
+    rows: &[(String, String)],
+    position: &mut ListState,
+    can_archive: bool,
+) {
+    //! Renders a popup list of (name, todoist colour) rows with the keys to change them
+    let mut keys = vec![
+        " a ".magenta(),
+        "add ─".into(),
+        " r ".magenta(),
+        "rename ─".into(),
+    ];
+    if can_archive {
+        keys.extend([" A ".magenta(), "archive ─".into()]);
+    }
+    keys.extend([" D ".magenta(), "delete ".into()]);
+    let block = Block::default()
+        .title(Title::from(title.bold().magenta()).alignment(Alignment::Center))
+        .title(
+            Title::from(Line::from(keys))
+                .alignment(Alignment::Center)
+                .position(Position::Bottom),
+        )
+        .borders(Borders::ALL)
+        .border_set(border::PLAIN);
+    let items: Vec<Line> = rows
+        .iter()
+        .map(|(name, color)| match color.is_empty() {
+            true => Line::from(name.as_str()),
+            false => Line::from(vec![
+                "● ".fg(todoist_color(color)),
+                Span::raw(name.as_str()),
+            ]),
+        })
+        .collect();
+
+    let popup = centered(area, 50, rows.len() as u16 + 2);
+    frame.render_widget(Clear, popup);
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(block)
+            .style(Style::new().blue())
+            .highlight_symbol("> ")
+            .highlight_style(Style::new().magenta())
+            .highlight_spacing(HighlightSpacing::Always),
+        popup,
+        position,
     );
-    frame.render_widget(
-        multiple_input_box(date, String::from(" date (ISO formatted) ")),
-        layout[3],
+}
+
+pub fn render_form(frame: &mut Frame, area: Rect, title: &str, fields: &[(&str, &str)]) {
+    //! Renders a popup form with an input box for each (title, input) field
+    let popup = centered(area, 50, fields.len() as u16 * 3 + 2);
+    let footer = Title::from(Line::from(vec![
+        " tab ".magenta(),
+        "next ─".into(),
+        " enter ".magenta(),
+        "save ─".into(),
+        " esc ".magenta(),
+        "cancel ".into(),
+    ]));
+    let block = Block::default()
+        .title(Title::from(title.bold().magenta()).alignment(Alignment::Center))
+        .title(
+            footer
+                .alignment(Alignment::Center)
+                .position(Position::Bottom),
+        )
+        .borders(Borders::ALL)
+        .border_set(border::PLAIN);
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Length(3); fields.len()])
+        .split(block.inner(popup));
+
+    frame.render_widget(Clear, popup);
+    frame.render_widget(block, popup);
+    for (index, (title, input)) in fields.iter().enumerate() {
+        frame.render_widget(multiple_input_box(input, title.to_string()), layout[index]);
+    }
+}
+
+pub fn render_picker(
+    frame: &mut Frame,
+    area: Rect,
+    query: &str,
+    rows: &[(String, String, bool)],
+    position: usize,
+) {
+    //! Renders the label picker popup from (name, todoist colour, chosen) rows,
+    //! with what has been typed to narrow them down as the title
+    let footer = Title::from(Line::from(vec![
+        " tab ".magenta(),
+        "to choose ─".into(),
+        " enter ".magenta(),
+        "to apply ─".into(),
+        " esc ".magenta(),
+        "to cancel ".into(),
+    ]));
+    let block = Block::default()
+        .title(Title::from(format!(" @{query} ").bold().magenta()).alignment(Alignment::Center))
+        .title(
+            footer
+                .alignment(Alignment::Center)
+                .position(Position::Bottom),
+        )
+        .borders(Borders::ALL)
+        .border_set(border::PLAIN);
+    let items: Vec<Line> = rows
+        .iter()
+        .map(|(name, color, chosen)| {
+            Line::from(vec![
+                match chosen {
+                    true => "[x] ".into(),
+                    false => "[ ] ".into(),
+                },
+                "● ".fg(todoist_color(color)),
+                Span::raw(name.as_str()),
+            ])
+        })
+        .collect();
+
+    let popup = centered(area, 50, (rows.len() as u16).clamp(1, 12) + 2);
+    frame.render_widget(Clear, popup);
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(block)
+            .style(Style::new().blue())
+            .highlight_symbol("> ")
+            .highlight_style(Style::new().magenta())
+            .highlight_spacing(HighlightSpacing::Always),
+        popup,
+        &mut ListState::default().with_selected(Some(position)),
     );
+}
+
+pub fn render_completed(
+    frame: &mut Frame,
+    area: Rect,
+    title: &str,
+    rows: &[String],
+    position: &mut ListState,
+) {
+    //! Renders the completed tasks view over the whole area
+    let footer = Title::from(Line::from(vec![
+        " enter ".magenta(),
+        "to restore ─".into(),
+        " h ".magenta(),
+        "older ─".into(),
+        " l ".magenta(),
+        "newer ─".into(),
+        " esc ".magenta(),
+        "to go back ".into(),
+    ]));
+    let block = Block::default()
+        .title(Title::from(title.bold().magenta()).alignment(Alignment::Center))
+        .title(
+            footer
+                .alignment(Alignment::Center)
+                .position(Position::Bottom),
+        )
+        .borders(Borders::ALL)
+        .border_set(border::PLAIN);
+    let items: Vec<Line> = match rows.is_empty() {
+        true => vec![Line::from("nothing completed")],
+        false => rows.iter().map(|row| Line::from(row.as_str())).collect(),
+    };
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(block)
+            .style(Style::new().blue())
+            .highlight_symbol("> ")
+            .highlight_style(Style::new().magenta())
+            .highlight_spacing(HighlightSpacing::Always),
+        area,
+        position,
+    );
+}
+
+pub fn render_chooser(
+    frame: &mut Frame,
+    area: Rect,
+    title: &str,
+    rows: &[String],
+    position: usize,
+) {
+    //! Renders the popup for choosing where a task goes, with what has been
+    //! typed to narrow the rows down in the title
+    let footer = Title::from(Line::from(vec![
+        " enter ".magenta(),
+        "to choose ─".into(),
+        " esc ".magenta(),
+        "to cancel ".into(),
+    ]));
+    let block = Block::default()
+        .title(Title::from(title.bold().magenta()).alignment(Alignment::Center))
+        .title(
+            footer
+                .alignment(Alignment::Center)
+                .position(Position::Bottom),
+        )
+        .borders(Borders::ALL)
+        .border_set(border::PLAIN);
+    let items: Vec<Line> = rows.iter().map(|row| Line::from(row.as_str())).collect();
+
+    let popup = centered(area, 60, (rows.len() as u16).clamp(1, 12) + 2);
+    frame.render_widget(Clear, popup);
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(block)
+            .style(Style::new().blue())
+            .highlight_symbol("> ")
+            .highlight_style(Style::new().magenta())
+            .highlight_spacing(HighlightSpacing::Always),
+        popup,
+        &mut ListState::default().with_selected(Some(position)),
+    );
+}
+
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    //! Rect of at least 40 wide in the middle of the area, for popups
+    let width = width.max(40).min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+pub fn split_status(area: Rect, show_status: bool) -> (Rect, Rect) {
+    //! Splits off a line at the bottom for the status bar when there is something to show
+    if !show_status {
+        return (area, Rect::new(area.x, area.y + area.height, area.width, 0));
+    }
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Fill(1), Constraint::Length(1)])
+        .split(area);
+    (layout[0], layout[1])
+}
+
+pub fn render_status(
+    frame: &mut Frame,
+    area: Rect,
+    message: &str,
+    can_retry: bool,
+    can_discard: bool,
+) {
+    //! Renders the status bar with a message and the keys to act on it
+    let mut line = vec![" ".into(), message.light_red(), " ─".into()];
+    if can_retry {
+        line.extend([" r ".magenta(), "to retry ─".into()]);
+    }
+    if can_discard {
+        line.extend([" X ".magenta(), "to drop queued changes ─".into()]);
+    }
+    line.extend([" x ".magenta(), "to dismiss ".into()]);
+    frame.render_widget(Paragraph::new(Line::from(line)), area);
+}
+
+pub fn render_toast(frame: &mut Frame, area: Rect, message: &str) {
+    //! Renders a short lived message in the status bar
+    frame.render_widget(Paragraph::new(format!(" {message}")).magenta(), area);
+}
+
+pub fn render_spinner(frame: &mut Frame, area: Rect, tick: usize) {
+    //! Draws a small spinner over the top right corner to show requests are in flight
+    const SPINNER: [&str; 4] = ["|", "/", "-", "\\"];
+    if area.width < 6 || area.height == 0 {
+        return;
+    }
+    let corner = Rect::new(area.x + area.width - 5, area.y, 3, 1);
     frame.render_widget(
-        multiple_input_box(priority, String::from(" priority (1-4 inclusive) ")),
-        layout[4],
+        Paragraph::new(format!(" {} ", SPINNER[tick % SPINNER.len()])).magenta(),
+        corner,
     );
 }
 
-fn list(items: &Vec<String>) -> List {
+pub fn split_sidebar(area: Rect) -> (Rect, Rect) {
+    //! Splits the area into the project sidebar and the rest
+    let layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![Constraint::Length(28), Constraint::Fill(1)])
+        .split(area);
+    (layout[0], layout[1])
+}
+
+pub fn render_projects(
+    frame: &mut Frame,
+    area: Rect,
+    rows: &[(String, String)],
+    position: &mut ListState,
+    focused: bool,
+) {
+    //! Renders the project tree sidebar from (text, todoist colour) rows
+    let footer = Title::from(Line::from(match focused {
+        true => vec![" space ".magenta(), "to fold ".into()],
+        false => vec![" p ".magenta(), "to focus ".into()],
+    }));
+    let block = Block::default()
+        .title(Title::from(" projects ".bold().magenta()).alignment(Alignment::Center))
+        .title(
+            footer
+                .alignment(Alignment::Center)
+                .position(Position::Bottom),
+        )
+        .borders(Borders::ALL)
+        .border_set(border::PLAIN)
+        .border_style(match focused {
+            true => Style::new().magenta(),
+            false => Style::new(),
+        });
+    let items: Vec<Line> = rows
+        .iter()
+        .map(|(text, color)| {
+            Line::from(vec![
+                "● ".fg(todoist_color(color)),
+                Span::raw(text.as_str()),
+            ])
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(block)
+        .style(Style::new().blue())
+        .highlight_symbol("> ")
+        .highlight_spacing(HighlightSpacing::Always);
+    let list = match focused {
+        true => list.highlight_style(Style::new().magenta()),
+        false => list,
+    };
+    frame.render_stateful_widget(list, area, position);
+}
+
+pub fn todoist_color(name: &str) -> Color {
+    //! Maps a todoist colour name onto the closest terminal colour
+    match name {
+        "berry_red" => Color::Rgb(0xb8, 0x25, 0x6f),
+        "red" => Color::Rgb(0xdb, 0x40, 0x35),
+        "orange" => Color::Rgb(0xff, 0x99, 0x33),
+        "yellow" => Color::Rgb(0xfa, 0xd0, 0x00),
+        "olive_green" => Color::Rgb(0xaf, 0xb8, 0x3b),
+        "lime_green" => Color::Rgb(0x7e, 0xcc, 0x49),
+        "green" => Color::Rgb(0x29, 0x94, 0x38),
+        "mint_green" => Color::Rgb(0x6a, 0xcc, 0xbc),
+        "teal" => Color::Rgb(0x15, 0x8f, 0xad),
+        "sky_blue" => Color::Rgb(0x14, 0xaa, 0xf5),
+        "light_blue" => Color::Rgb(0x96, 0xc3, 0xeb),
+        "blue" => Color::Rgb(0x40, 0x73, 0xff),
+        "grape" => Color::Rgb(0x88, 0x4d, 0xff),
+        "violet" => Color::Rgb(0xaf, 0x38, 0xeb),
+        "lavender" => Color::Rgb(0xeb, 0x96, 0xeb),
+        "magenta" => Color::Rgb(0xe0, 0x51, 0x94),
+        "salmon" => Color::Rgb(0xff, 0x8d, 0x85),
+        "charcoal" => Color::Rgb(0x80, 0x80, 0x80),
+        "grey" => Color::Rgb(0xb8, 0xb8, 0xb8),
+        "taupe" => Color::Rgb(0xcc, 0xac, 0x93),
+        _ => Color::Reset,
+    }
+}
+
+fn list<'a>(items: &'a [ListEntry], title: &'a str) -> List<'a> {
     // setup formatting
-    let header = Title::from(" todo ".bold().magenta());
+    let header = Title::from(title.bold().magenta());
     let footer = Title::from(Line::from(vec![
         " c ".magenta(),
         "to complete ─ ".into(),
         "n ".magenta(),
         "to create ─ ".into(),
         "u ".magenta(),
-        "to update ─ ".into(),
+        "to undo ─ ".into(),
         "e ".magenta(),
         "to edit ".into(),
     ]));
@@ -110,7 +512,15 @@ fn list(items: &Vec<String>) -> List {
         .borders(Borders::ALL)
         .border_set(border::PLAIN);
 
-    List::new(items.to_owned())
+    let items: Vec<ListItem> = items
+        .iter()
+        .map(|entry| match entry.header {
+            true => ListItem::new(entry.text.as_str()).bold().magenta(),
+            false => ListItem::new(entry.text.as_str()),
+        })
+        .collect();
+
+    List::new(items)
         .block(block)
         .highlight_symbol("> ")
         .highlight_style(Style::new().magenta())
@@ -118,7 +528,7 @@ fn list(items: &Vec<String>) -> List {
         .highlight_spacing(HighlightSpacing::Always)
 }
 
-fn input_box(current_input: &str, title: String) -> Paragraph {
+fn input_box(current_input: &str, title: String) -> Paragraph<'_> {
     let footer = Title::from(Line::from(vec![
         " delete ".light_blue(),
         "to exit mode ─ ".into(),
@@ -138,15 +548,19 @@ fn input_box(current_input: &str, title: String) -> Paragraph {
         .wrap(Wrap { trim: true })
 }
 
-fn multiple_input_box(current_input: &str, title: String) -> Paragraph {
+fn multiple_input_box(current_input: &str, title: String) -> Paragraph<'_> {
     Paragraph::new(current_input)
         .style(Style::default().fg(Color::White))
         .block(Block::bordered().title(title.magenta()).light_blue())
         .wrap(Wrap { trim: true })
 }
 
-fn infomation_panel(taskinfo: &str) -> Paragraph {
+fn infomation_panel(taskinfo: &str) -> Paragraph<'_> {
     let footer = Title::from(Line::from(vec![
+        " a ".magenta(),
+        "to comment ─".into(),
+        " R ".magenta(),
+        "to remind ─".into(),
         " <backspace> ".magenta(),
         "to close ".into(),
     ]));
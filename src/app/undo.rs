@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+use super::{
+    api::{Command, Resource, SyncCommand, Task},
+    App,
+};
+
+/// A change that can be taken back, the newest is undone first
+pub struct Undo {
+    /// what was done, e.g. "task completed"
+    pub action: &'static str,
+    /// copies of the tasks from before the change, put back locally when undone
+    pub tasks: Vec<Task>,
+    /// commands reversing the change on the server
+    pub commands: Vec<SyncCommand>,
+}
+
+impl Undo {
+    pub fn completed(tasks: Vec<Task>) -> Undo {
+        //! Undoes completing the first task, along with any sub-tasks completed with it.
+        //! Recurring tasks were moved to their next date rather than closed,
+        //! so they're moved back to this one
+        let commands = match tasks.first() {
+            Some(task) if task.is_recurring() => vec![task.restore_command()],
+            _ => tasks
+                .iter()
+                .map(|task| Command::ItemUncomplete { id: task.get_id() })
+                .collect(),
+        };
+        Undo {
+            action: "task completed",
+            tasks,
+            commands: commands.into_iter().map(SyncCommand::new).collect(),
+        }
+    }
+
+    pub fn deleted(tasks: Vec<Task>) -> Undo {
+        //! Undoes deleting tasks by adding copies of them under temp ids, as deleted
+        //! tasks can't be brought back. Parents need to come before their sub-tasks
+        let mut temp_ids: HashMap<String, String> = HashMap::new();
+        let mut copies = Vec::new();
+        let mut commands = Vec::new();
+        for task in tasks {
+            let temp_id = uuid::Uuid::new_v4().to_string();
+            let mut copy = task.duplicate(temp_id.clone());
+            // sub-tasks go under the copy of their parent
+            copy.remap(&temp_ids);
+            temp_ids.insert(task.get_id(), temp_id.clone());
+            commands.push(SyncCommand::new(copy.add_command()).with_temp_id(temp_id));
+            copies.push(copy);
+        }
+        Undo {
+            action: "task deleted",
+            tasks: copies,
+            commands,
+        }
+    }
+}
+
+impl App {
+    pub(super) fn record_undo(&mut self, undo: Undo) {
+        self.toast(format!("{} ─ press u to undo", undo.action));
+        self.undo_stack.push(undo);
+    }
+
+    pub(super) fn undo(&mut self) {
+        //! Reverses the last change, locally straight away and queued for the server
+        let Some(undo) = self.undo_stack.pop() else {
+            self.toast(String::from("nothing to undo"));
+            return;
+        };
+        self.update_tasks(|tasks| {
+            for task in undo.tasks {
+                match tasks.iter_mut().find(|x| x.id() == task.id()) {
+                    Some(current) => *current = task,
+                    None => tasks.push(task),
+                }
+            }
+        });
+        self.queue(undo.commands);
+        self.toast(format!("undone: {}", undo.action));
+    }
+}
@@ -0,0 +1,252 @@
+use super::{
+    api::{Resource, Section},
+    ui, App,
+};
+
+/// A row of the task list
+pub enum Row {
+    /// heading of a project or section, key is used to fold it
+    Header {
+        key: String,
+        title: String,
+        depth: usize,
+        count: usize,
+    },
+    /// index into App.tasks, and how deep it is in the sub-task tree
+    Task { index: usize, depth: usize },
+}
+
+impl Row {
+    pub fn task(&self) -> Option<usize> {
+        match self {
+            Row::Task { index, .. } => Some(*index),
+            Row::Header { .. } => None,
+        }
+    }
+}
+
+impl App {
+    pub(super) fn build_rows(&self) -> Vec<Row> {
+        //! Task list rows, grouped under a heading for each project (unless the list is
+        //! filtered to one project) and then under a heading for each of its sections
+        let listed: Vec<usize> = (0..self.tasks.len())
+            .filter(|&index| match &self.project_filter {
+                Some(project_id) => self.tasks[index].project_id() == project_id,
+                None => true,
+            })
+            .filter(|&index| {
+                let labels = self.tasks[index].labels();
+                self.label_filter.iter().all(|label| labels.contains(label))
+            })
+            .filter(|&index| !self.deadline_filter || self.tasks[index].deadline_date().is_some())
+            .collect();
+
+        let mut rows = Vec::new();
+        match &self.project_filter {
+            Some(project_id) => self.push_project_rows(&mut rows, project_id, &listed, 0),
+            None => {
+                let mut projects: Vec<(String, String)> = self
+                    .project_order()
+                    .iter()
+                    .map(|&index| {
+                        let project = &self.projects[index];
+                        (project.id.clone(), project.name.clone())
+                    })
+                    .collect();
+                // tasks in projects that haven't synced still need showing somewhere
+                for &index in &listed {
+                    let project_id = self.tasks[index].project_id();
+                    if !projects.iter().any(|(id, _)| id == project_id) {
+                        projects.push((project_id.to_string(), String::from("unknown project")));
+                    }
+                }
+                for (project_id, name) in projects {
+                    let count = listed
+                        .iter()
+                        .filter(|&&index| self.tasks[index].project_id() == project_id)
+                        .count();
+                    if count == 0 {
+                        continue;
+                    }
+                    let key = format!("project/{project_id}");
+                    let folded = self.folded.contains(&key);
+                    rows.push(Row::Header {
+                        key,
+                        title: name,
+                        depth: 0,
+                        count,
+                    });
+                    if !folded {
+                        self.push_project_rows(&mut rows, &project_id, &listed, 1);
+                    }
+                }
+            }
+        }
+        rows
+    }
+
+    fn push_project_rows(
+        &self,
+        rows: &mut Vec<Row>,
+        project_id: &str,
+        listed: &[usize],
+        depth: usize,
+    ) {
+        //! Rows for one project, tasks without a section first then each section in order
+        let mut sections: Vec<&Section> = self
+            .sections
+            .iter()
+            .filter(|section| section.project_id == project_id)
+            .collect();
+        sections.sort_by_key(|section| section.section_order);
+        let in_project = |index: &&usize| self.tasks[**index].project_id() == project_id;
+
+        // tasks in sections that haven't synced are treated as having no section
+        let unsectioned = listed.iter().filter(in_project).filter(|&&index| {
+            self.tasks[index]
+                .section_id()
+                .is_none_or(|id| !sections.iter().any(|section| section.id() == id))
+        });
+        let unsectioned: Vec<usize> = unsectioned.copied().collect();
+        self.push_task_tree(rows, &unsectioned);
+
+        for section in sections {
+            let tasks: Vec<usize> = listed
+                .iter()
+                .filter(in_project)
+                .filter(|&&index| self.tasks[index].section_id() == Some(section.id()))
+                .copied()
+                .collect();
+            let key = format!("section/{}", section.id);
+            let folded = self.folded.contains(&key);
+            rows.push(Row::Header {
+                key,
+                title: section.name.clone(),
+                depth,
+                count: tasks.len(),
+            });
+            if !folded {
+                self.push_task_tree(rows, &tasks);
+            }
+        }
+    }
+
+    fn push_task_tree(&self, rows: &mut Vec<Row>, group: &[usize]) {
+        //! Rows for a group of tasks with sub-tasks nested under their parent.
+        //! Top level tasks keep the order of the group, sub-tasks are in child_order
+        let in_group = |id: &str| group.iter().any(|&index| self.tasks[index].id() == id);
+        let children = |parent_id: &str| {
+            let mut children: Vec<usize> = group
+                .iter()
+                .copied()
+                .filter(|&index| self.tasks[index].parent_id() == Some(parent_id))
+                .collect();
+            children.sort_by_key(|&index| self.tasks[index].child_order());
+            children
+        };
+
+        // depth first walk, stack holds (task index, depth) in reverse order
+        let mut stack: Vec<(usize, usize)> = group
+            .iter()
+            .rev()
+            .filter(|&&index| self.tasks[index].parent_id().is_none_or(|id| !in_group(id)))
+            .map(|&index| (index, 0))
+            .collect();
+        while let Some((index, depth)) = stack.pop() {
+            rows.push(Row::Task { index, depth });
+            let id = self.tasks[index].id();
+            if !self.folded.contains(&format!("task/{id}")) {
+                stack.extend(children(id).iter().rev().map(|&x| (x, depth + 1)));
+            }
+        }
+    }
+
+    pub(super) fn rows_text(&self, width: u16) -> Vec<ui::ListEntry> {
+        //! Task list rows as text for the list widget
+        self.rows
+            .iter()
+            .map(|row| match row {
+                Row::Task { index, depth } => {
+                    let task = &self.tasks[*index];
+                    let marker = match (
+                        self.has_children(task.id()),
+                        self.folded.contains(&format!("task/{}", task.id())),
+                    ) {
+                        (false, _) => "",
+                        (true, true) => "▸ ",
+                        (true, false) => "▾ ",
+                    };
+                    let prefix = format!("{}{marker}", "  ".repeat(*depth));
+                    ui::ListEntry {
+                        text: task.to_list_string(width, &prefix, &self.due_format),
+                        header: false,
+                    }
+                }
+                Row::Header {
+                    key,
+                    title,
+                    depth,
+                    count,
+                } => {
+                    let marker = match self.folded.contains(key) {
+                        true => "▸",
+                        false => "▾",
+                    };
+                    ui::ListEntry {
+                        text: format!("{}{marker} {title} ({count})", "  ".repeat(*depth)),
+                        header: true,
+                    }
+                }
+            })
+            .collect()
+    }
+
+    pub(super) fn fold_selected(&mut self) {
+        //! Unfolds a folded heading next to the selected task, with only headings
+        //! between them, otherwise folds the heading the selected task is listed under
+        let selected = self
+            .position
+            .selected()
+            .filter(|_| self.selected_task().is_some());
+        let folded = |row: &Row| match row {
+            Row::Header { key, .. } if self.folded.contains(key) => Some(key.clone()),
+            _ => None,
+        };
+        let next_to = match selected {
+            Some(current) => {
+                let below = self.rows[current + 1..]
+                    .iter()
+                    .take_while(|row| row.task().is_none())
+                    .find_map(folded);
+                let above = self.rows[..current]
+                    .iter()
+                    .rev()
+                    .take_while(|row| row.task().is_none())
+                    .find_map(folded);
+                below.or(above)
+            }
+            // every task is folded away, so there is nothing to be next to
+            None => self.rows.iter().find_map(folded),
+        };
+        let selected_id = self.selected_task().map(|index| self.tasks[index].get_id());
+        if let Some(key) = next_to {
+            self.folded.remove(&key);
+            self.refresh_view(selected_id);
+            return;
+        }
+        let Some(current) = selected else {
+            return;
+        };
+        let heading = self.rows[..=current]
+            .iter()
+            .rev()
+            .find_map(|row| match row {
+                Row::Header { key, .. } => Some(key.clone()),
+                Row::Task { .. } => None,
+            });
+        if let Some(key) = heading {
+            self.folded.insert(key);
+            self.refresh_view(None);
+        }
+    }
+}
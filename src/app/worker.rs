@@ -0,0 +1,113 @@
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+
+use super::api::{
+    Api, ApiError, BatchResult, CommandBatch, CompletedTask, SyncCommand, SyncData, Task,
+};
+
+#[derive(Clone)]
+/// Work the app can hand off to the worker thread
+pub enum Request {
+    /// Sync resources using the given sync token ("*" for a full sync)
+    Sync(String),
+    /// Task changes queued in the outbox, sent in the order they were made
+    Replay(Vec<SyncCommand>),
+    /// Add a task from quick add text, with its shorthand parsed by the server.
+    /// Holds the request id, kept for retries so the task is only added once, and the text
+    QuickAdd(String, String),
+    /// Tasks completed between two UTC times
+    FetchCompleted(String, String),
+    /// Any other sync commands, sent together
+    Batch(CommandBatch),
+}
+
+/// Result of a finished request, sent back to the app
+pub enum Response {
+    /// Holds the sync token the sync was requested with, alongside the result
+    Synced(String, Result<SyncData, ApiError>),
+    /// Holds the replayed commands, alongside how each of them went
+    Replayed(Vec<SyncCommand>, Result<BatchResult, ApiError>),
+    /// Holds the request id and quick add text, alongside the added task
+    Added(String, String, Result<Task, ApiError>),
+    /// Holds the times the completed tasks were fetched between, alongside the tasks
+    FetchedCompleted(String, String, Result<Vec<CompletedTask>, ApiError>),
+    /// Holds the sent batch, alongside the new sync token
+    Batched(CommandBatch, Result<String, ApiError>),
+}
+
+/// Handle to a background thread that owns the API client and makes all
+/// requests, so the UI can keep drawing while they are in flight
+pub struct Worker {
+    requests: Sender<Request>,
+    responses: Receiver<Response>,
+    in_flight: usize,
+}
+
+impl Worker {
+    pub fn spawn(client: Api) -> Worker {
+        //! Starts the worker thread, consuming the API client it will use.
+        //! The thread stops once the Worker is dropped.
+        let (request_sender, request_receiver) = mpsc::channel::<Request>();
+        let (response_sender, response_receiver) = mpsc::channel::<Response>();
+        thread::spawn(move || {
+            for request in request_receiver {
+                if response_sender.send(handle(&client, request)).is_err() {
+                    break;
+                }
+            }
+        });
+        Worker {
+            requests: request_sender,
+            responses: response_receiver,
+            in_flight: 0,
+        }
+    }
+
+    pub fn send(&mut self, request: Request) {
+        //! Queue a request, requests are made one at a time in the order they are sent
+        if self.requests.send(request).is_ok() {
+            self.in_flight += 1;
+        }
+    }
+
+    pub fn try_recv(&mut self) -> Option<Response> {
+        //! Returns a finished response if there is one, without blocking
+        match self.responses.try_recv() {
+            Ok(response) => {
+                self.in_flight -= 1;
+                Some(response)
+            }
+            Err(TryRecvError::Empty | TryRecvError::Disconnected) => None,
+        }
+    }
+
+    pub fn is_busy(&self) -> bool {
+        //! True while any request has not had its response collected
+        self.in_flight > 0
+    }
+}
+
+fn handle(client: &Api, request: Request) -> Response {
+    match request {
+        Request::Sync(sync_token) => {
+            let result = client.sync(&sync_token);
+            Response::Synced(sync_token, result)
+        }
+        Request::FetchCompleted(since, until) => {
+            let result = client.completed_tasks(&since, &until);
+            Response::FetchedCompleted(since, until, result)
+        }
+        Request::QuickAdd(request_id, text) => {
+            let result = client.quick_add(text.clone(), &request_id);
+            Response::Added(request_id, text, result)
+        }
+        Request::Replay(commands) => {
+            let result = client.replay(commands.clone());
+            Response::Replayed(commands, result)
+        }
+        Request::Batch(batch) => {
+            let result = client.send_batch(batch.clone());
+            Response::Batched(batch, result)
+        }
+    }
+}
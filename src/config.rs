@@ -0,0 +1,59 @@
+use std::{env, fs, path::PathBuf};
+
+use serde::Deserialize;
+
+use crate::app::{DueFormat, RetryPolicy};
+
+/// Default base url of the unified Todoist API v1
+pub const DEFAULT_API_URL: &str = "https://api.todoist.com/api/v1";
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+/// Settings read from the config file, every key is optional
+pub struct Config {
+    /// Base url all API requests are made relative to, e.g. a local mock server
+    pub api_url: Option<String>,
+    /// [retry] table, how failed requests are retried
+    pub retry: RetryPolicy,
+    /// [due] table, how due dates are shown
+    pub due: DueFormat,
+}
+
+impl Config {
+    pub fn load() -> Result<Config, String> {
+        //! Reads the config file if there is one, falling back to defaults if not.
+        //! Environment variables take priority over keys in the file.
+        let mut config = match config_path() {
+            Some(path) if path.exists() => {
+                let contents = fs::read_to_string(&path)
+                    .map_err(|error| format!("could not read {}: {error}", path.display()))?;
+                toml::from_str::<Config>(&contents)
+                    .map_err(|error| format!("could not parse {}: {error}", path.display()))?
+            }
+            _ => Config::default(),
+        };
+        config.due.validate()?;
+        if let Ok(api_url) = env::var("TODOIST_API_URL") {
+            config.api_url = Some(api_url);
+        }
+        Ok(config)
+    }
+
+    pub fn api_url(&self) -> String {
+        //! The configured API base url without a trailing slash
+        self.api_url
+            .as_deref()
+            .unwrap_or(DEFAULT_API_URL)
+            .trim_end_matches('/')
+            .to_string()
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    //! $XDG_CONFIG_HOME/todoist-rs/config.toml, or ~/.config/todoist-rs/config.toml
+    let config_home = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_home.join("todoist-rs").join("config.toml"))
+}
@@ -1,14 +1,12 @@
 use std::env;
 mod app;
+mod config;
 mod tui;
 
-// new api https://developer.todoist.com/api/v1 update needed to comply
-// also i'm gonna finally do non-blocking SOON i swear
-
-fn exit_in_tui(message: &str, code: u16) {
+fn exit_in_tui(message: &str, code: i32) {
     tui::restore().unwrap();
     println!("{message}");
-    std::process::exit(code as i32);
+    std::process::exit(code);
 }
 
 fn main() {
@@ -20,6 +18,14 @@ fn main() {
         }
         Ok(token) => token,
     };
+    // read config file and environment
+    let config = match config::Config::load() {
+        Err(error) => {
+            println!("Error loading config: {error}");
+            std::process::exit(8);
+        }
+        Ok(config) => config,
+    };
     // initialise terminal ready for render
     let mut terminal = match tui::init() {
         Ok(termbackend) => termbackend,
@@ -31,18 +37,32 @@ fn main() {
         }
     };
     // initialise app and api client
-    let mut app_client = app::App::new(token);
+    let mut app_client = app::App::new(token, config);
     // run application
-    match app_client.run(&mut terminal) {
-        Ok(_) => {},
-        Err(2) => {exit_in_tui("Response from server was unexpected, and could not be parsed from JSON into the nessasary objects", 2)}
-        Err(3) => {exit_in_tui("HTTP POST failed, maybe check your internet connection?", 3)}
-        Err(4) => {exit_in_tui("Crossterm incorrectly read an event, is your terminal supported by crossterm?", 4)}
-        Err(147) => {exit_in_tui("Authentication failed in an API request, please check that your token (echo $TODOIST_TOKEN) is valid", 147)}
-        Err(401) => {exit_in_tui("Authentication failed in an API request, please check that your token (echo $TODOIST_TOKEN) is valid", 401)}
-        Err(x) if x > 200 => {exit_in_tui("HTTP error code if between 200 and 600, otherwise unknown (inspect error code with $?, $status or whatever your shell uses to find out what one) (if the exit code is 147 it's probably your token being invalid idk why my error stuff isn't working.)", x)}
-        Err(x) => {exit_in_tui("unknown error :(", x)}
-    };
+    if let Err(error) = app_client.run(&mut terminal) {
+        let (message, code) = match &error {
+            app::Error::Terminal(_) => (
+                "Crossterm incorrectly read an event, is your terminal supported by crossterm?",
+                4,
+            ),
+            app::Error::Api(api_error) if api_error.is_auth() => (
+                "Authentication failed in an API request, please check that your token (echo $TODOIST_TOKEN) is valid",
+                6,
+            ),
+            app::Error::Api(app::ApiError::Network { .. }) => {
+                ("HTTP POST failed, maybe check your internet connection?", 3)
+            }
+            app::Error::Api(app::ApiError::Parse { .. }) => (
+                "Response from server was unexpected, and could not be parsed from JSON into the nessasary objects",
+                2,
+            ),
+            app::Error::Api(app::ApiError::Http { .. }) => ("Todoist returned an error", 7),
+            app::Error::Api(app::ApiError::Rejected { .. }) => {
+                ("Todoist rejected a change made in the app", 9)
+            }
+        };
+        exit_in_tui(&format!("{message}\n{error}"), code);
+    }
     // return terminal to default state
     exit_in_tui("bye bye!", 0);
 }